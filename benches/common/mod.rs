@@ -0,0 +1,9 @@
+use crossbeam::sync::WaitGroup;
+use std::hint::black_box;
+
+pub fn tiny_job(wg: WaitGroup) -> impl FnOnce() + Send + 'static {
+    move || {
+        black_box(1 + 1);
+        drop(wg);
+    }
+}
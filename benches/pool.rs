@@ -0,0 +1,159 @@
+mod common;
+
+use common::tiny_job;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::sync::WaitGroup;
+use simple_http_server::{ThreadPool, ThreadPoolBuilder};
+use std::{hint::black_box, sync::Arc, thread};
+
+const WORKERS: usize = 4;
+const JOBS: usize = 10_000;
+// Spawning a thread per job is slow enough that fewer jobs keep the run short.
+const SPAWN_JOBS: usize = 1_000;
+const ROUND_TRIPS: usize = 200;
+const ITEMS: usize = 100_000;
+// Fits in the `ring-buffer` feature's local queue, so no job spills to the lanes.
+const LOCAL_BATCH: usize = 200;
+
+fn submit_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("submit_throughput");
+    group.throughput(Throughput::Elements(JOBS as u64));
+    let pools = [
+        ("unbounded", ThreadPool::new(WORKERS)),
+        (
+            "bounded_64",
+            ThreadPool::build_bounded(WORKERS, 64).unwrap(),
+        ),
+    ];
+    for (name, pool) in &pools {
+        group.bench_function(BenchmarkId::new(*name, JOBS), |b| {
+            b.iter(|| {
+                let wg = WaitGroup::new();
+                for _ in 0..JOBS {
+                    pool.execute(tiny_job(wg.clone())).unwrap();
+                }
+                wg.wait();
+            })
+        });
+    }
+    group.finish();
+}
+
+// Jobs a worker submits to its own pool, the only ones that go through the local
+// queue the `ring-buffer` feature swaps out. One worker, so there is never an idle
+// peer to hand the job to instead.
+fn local_queue(c: &mut Criterion) {
+    let mut group = c.benchmark_group("local_queue");
+    group.throughput(Throughput::Elements(JOBS as u64));
+    let pool = Arc::new(ThreadPool::new(1));
+    group.bench_function(BenchmarkId::new("nested", JOBS), |b| {
+        b.iter(|| {
+            for _ in 0..JOBS / LOCAL_BATCH {
+                let wg = WaitGroup::new();
+                let inner = Arc::clone(&pool);
+                let outer = wg.clone();
+                pool.execute(move || {
+                    for _ in 0..LOCAL_BATCH {
+                        inner.execute(tiny_job(outer.clone())).unwrap();
+                    }
+                })
+                .unwrap();
+                wg.wait();
+            }
+        })
+    });
+    group.finish();
+}
+
+// Round trips through `spawn` and `join` from several threads at once, the time per
+// element is the mean latency of one round trip.
+fn contention_latency(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contention_latency");
+    let pool = ThreadPool::new(WORKERS);
+    for submitters in [1, 4, 16] {
+        group.throughput(Throughput::Elements((submitters * ROUND_TRIPS) as u64));
+        group.bench_function(BenchmarkId::from_parameter(submitters), |b| {
+            b.iter(|| {
+                thread::scope(|s| {
+                    for _ in 0..submitters {
+                        s.spawn(|| {
+                            for i in 0..ROUND_TRIPS {
+                                let handle = pool.spawn(move || black_box(i)).unwrap();
+                                handle.join().unwrap();
+                            }
+                        });
+                    }
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn fan_out_fan_in(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fan_out_fan_in");
+    group.throughput(Throughput::Elements(ITEMS as u64));
+    let pool = ThreadPool::new(WORKERS);
+    let items: Vec<u64> = (0..ITEMS as u64).collect();
+    group.bench_function(BenchmarkId::new("par_map", ITEMS), |b| {
+        b.iter(|| pool.par_map(&items, |x| x.wrapping_mul(31)).len())
+    });
+    group.bench_function(BenchmarkId::new("par_chunks", ITEMS), |b| {
+        b.iter(|| {
+            pool.par_chunks(&items, 1024, |chunk| chunk.iter().sum::<u64>())
+                .into_iter()
+                .sum::<u64>()
+        })
+    });
+    group.bench_function(BenchmarkId::new("scope_per_item", ITEMS), |b| {
+        b.iter(|| {
+            let mut out = vec![0; ITEMS];
+            pool.scope(|s| {
+                for (x, slot) in items.iter().zip(&mut out) {
+                    s.spawn(move || *slot = x.wrapping_mul(31)).unwrap();
+                }
+            });
+            out
+        })
+    });
+    group.bench_function(BenchmarkId::new("sequential", ITEMS), |b| {
+        b.iter(|| items.iter().map(|x| x.wrapping_mul(31)).collect::<Vec<_>>())
+    });
+    group.finish();
+}
+
+fn vs_thread_spawn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vs_thread_spawn");
+    group.throughput(Throughput::Elements(SPAWN_JOBS as u64));
+    let pool = ThreadPoolBuilder::new().size(WORKERS).build().unwrap();
+    group.bench_function(BenchmarkId::new("thread_pool", SPAWN_JOBS), |b| {
+        b.iter(|| {
+            let wg = WaitGroup::new();
+            for _ in 0..SPAWN_JOBS {
+                pool.execute(tiny_job(wg.clone())).unwrap();
+            }
+            wg.wait();
+        })
+    });
+    group.bench_function(BenchmarkId::new("thread_spawn", SPAWN_JOBS), |b| {
+        b.iter(|| {
+            let handles: Vec<_> = (0..SPAWN_JOBS)
+                .map(|i| thread::spawn(move || black_box(i)))
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    submit_throughput,
+    local_queue,
+    contention_latency,
+    fan_out_fan_in,
+    vs_thread_spawn
+);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+mod common;
+
+use common::tiny_job;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::{channel, sync::WaitGroup};
+use simple_http_server::ThreadPool;
+use std::{
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+const WORKERS: usize = 4;
+const JOBS: usize = 10_000;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// The receive path ThreadPool used before workers had their own receivers: every
+// dequeue takes a shared lock and holds it across the blocking recv.
+struct MutexPool {
+    workers: Vec<JoinHandle<()>>,
+    sender: Option<channel::Sender<Job>>,
+}
+
+impl MutexPool {
+    fn new(size: usize) -> MutexPool {
+        let (tx, rx) = channel::unbounded::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let workers = (0..size)
+            .map(|_| {
+                let rx = Arc::clone(&rx);
+                thread::spawn(move || loop {
+                    let message = rx.lock().unwrap().recv();
+                    match message {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        MutexPool {
+            workers,
+            sender: Some(tx),
+        }
+    }
+
+    fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.sender.as_ref().unwrap().send(Box::new(f)).unwrap();
+    }
+}
+
+impl Drop for MutexPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+    }
+}
+
+fn tiny_jobs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tiny_jobs");
+    group.throughput(Throughput::Elements(JOBS as u64));
+
+    let pool = ThreadPool::new(WORKERS);
+    group.bench_function(BenchmarkId::new("thread_pool", JOBS), |b| {
+        b.iter(|| {
+            let wg = WaitGroup::new();
+            for _ in 0..JOBS {
+                pool.execute(tiny_job(wg.clone())).unwrap();
+            }
+            wg.wait();
+        })
+    });
+
+    let mutex_pool = MutexPool::new(WORKERS);
+    group.bench_function(BenchmarkId::new("mutex_receiver", JOBS), |b| {
+        b.iter(|| {
+            let wg = WaitGroup::new();
+            for _ in 0..JOBS {
+                mutex_pool.execute(tiny_job(wg.clone()));
+            }
+            wg.wait();
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, tiny_jobs);
+criterion_main!(benches);
@@ -0,0 +1,184 @@
+use std::{sync::Arc, thread, time::Duration};
+
+use crate::{Autoscale, PoolCreationError, Scaling, StatefulPool, ThreadPool};
+
+type Hook = Arc<dyn Fn(usize) + Send + Sync + 'static>;
+
+#[derive(Clone, Default)]
+pub(crate) struct WorkerConfig {
+    pub(crate) name_prefix: Option<String>,
+    pub(crate) stack_size: Option<usize>,
+    pub(crate) on_start: Option<Hook>,
+    pub(crate) on_stop: Option<Hook>,
+    pub(crate) cpus: Vec<usize>,
+    pub(crate) timings: bool,
+    pub(crate) slow_threshold: Option<Duration>,
+    #[cfg(feature = "deterministic")]
+    pub(crate) seed: Option<u64>,
+}
+
+impl WorkerConfig {
+    pub(crate) fn thread_builder(&self, id: usize) -> thread::Builder {
+        let mut builder = thread::Builder::new();
+        if let Some(prefix) = &self.name_prefix {
+            builder = builder.name(format!("{prefix}-{id}"));
+        }
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        builder
+    }
+
+    pub(crate) fn cpu_for(&self, id: usize) -> Option<usize> {
+        match self.cpus.len() {
+            0 => None,
+            n => Some(self.cpus[id % n]),
+        }
+    }
+}
+
+pub struct ThreadPoolBuilder {
+    size: Option<usize>,
+    capacity: Option<usize>,
+    autoscale: Option<Autoscale>,
+    config: WorkerConfig,
+}
+
+impl Default for ThreadPoolBuilder {
+    fn default() -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new()
+    }
+}
+
+impl ThreadPoolBuilder {
+    pub fn new() -> ThreadPoolBuilder {
+        ThreadPoolBuilder {
+            size: None,
+            capacity: None,
+            autoscale: None,
+            config: WorkerConfig::default(),
+        }
+    }
+
+    // Defaults to the number of available CPUs, or `autoscale.min` when autoscaling.
+    // When autoscaling, it has to lie between `autoscale.min` and `autoscale.max`.
+    pub fn size(mut self, size: usize) -> ThreadPoolBuilder {
+        self.size = Some(size);
+        self
+    }
+
+    // Caps the jobs waiting across all priorities together.
+    pub fn queue_capacity(mut self, capacity: usize) -> ThreadPoolBuilder {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    pub fn autoscale(mut self, autoscale: Autoscale) -> ThreadPoolBuilder {
+        self.autoscale = Some(autoscale);
+        self
+    }
+
+    // Workers are named `{prefix}-{id}`, e.g. `http-worker-3`.
+    pub fn thread_name(mut self, prefix: impl Into<String>) -> ThreadPoolBuilder {
+        self.config.name_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn stack_size(mut self, bytes: usize) -> ThreadPoolBuilder {
+        self.config.stack_size = Some(bytes);
+        self
+    }
+
+    // Runs on the worker thread with its id before it takes any jobs.
+    pub fn on_thread_start<F>(mut self, f: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.config.on_start = Some(Arc::new(f));
+        self
+    }
+
+    // Runs on the worker thread with its id as it exits.
+    pub fn on_thread_stop<F>(mut self, f: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.config.on_stop = Some(Arc::new(f));
+        self
+    }
+
+    // Records queue wait and execution time for every job. Costs a few clock
+    // reads per job, so it is off by default.
+    pub fn track_timings(mut self, enabled: bool) -> ThreadPoolBuilder {
+        self.config.timings = enabled;
+        self
+    }
+
+    // Warns about jobs still running after `threshold`, naming the worker and the
+    // job's label, and reports the pool as starved when every worker has one.
+    pub fn slow_job_threshold(mut self, threshold: Duration) -> ThreadPoolBuilder {
+        self.config.slow_threshold = Some(threshold);
+        self
+    }
+
+    // Pins worker `id` to `cpus[id % cpus.len()]`. Ids must be below `CPU_SETSIZE`.
+    #[cfg(target_os = "linux")]
+    pub fn cpu_affinity(mut self, cpus: impl IntoIterator<Item = usize>) -> ThreadPoolBuilder {
+        self.config.cpus = cpus.into_iter().collect();
+        self
+    }
+
+    // Starts no worker threads. Jobs sit in the queue until `run_until_idle` runs
+    // them on the calling thread, picking among the highest priority ones in an
+    // order fixed by `seed`. A full queue fails submissions with `QueueFull`
+    // rather than blocking.
+    #[cfg(feature = "deterministic")]
+    pub fn deterministic(mut self, seed: u64) -> ThreadPoolBuilder {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn build(self) -> Result<ThreadPool, PoolCreationError> {
+        let scaling = match self.autoscale {
+            Some(autoscale) => {
+                autoscale.validate()?;
+                Scaling::from(autoscale)
+            }
+            None => Scaling::fixed(self.size.unwrap_or_else(default_size)),
+        };
+        let size = self.size.unwrap_or(scaling.min);
+        if size == 0 {
+            return Err(PoolCreationError::SizeZero);
+        }
+        if !(scaling.min..=scaling.max).contains(&size) {
+            return Err(PoolCreationError::SizeOutOfRange {
+                size,
+                min: scaling.min,
+                max: scaling.max,
+            });
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(&cpu) = self
+            .config
+            .cpus
+            .iter()
+            .find(|&&cpu| cpu >= libc::CPU_SETSIZE as usize)
+        {
+            return Err(PoolCreationError::InvalidCpu(cpu));
+        }
+        ThreadPool::start(size, self.capacity, scaling, self.config)
+    }
+
+    // Builds a pool where every worker owns a `S` made by `factory`, see `StatefulPool`.
+    pub fn build_with_state<S, F>(self, factory: F) -> Result<StatefulPool<S>, PoolCreationError>
+    where
+        S: 'static,
+        F: Fn() -> S + Send + Sync + 'static,
+    {
+        Ok(StatefulPool::new(self.build()?, Arc::new(factory)))
+    }
+}
+
+fn default_size() -> usize {
+    thread::available_parallelism().map_or(4, |n| n.get())
+}
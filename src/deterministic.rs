@@ -0,0 +1,61 @@
+use std::{collections::VecDeque, iter, sync::Mutex};
+
+use crate::{queue::Queue, stats::WorkerCounters, Priority, Task};
+
+// Runs a worker-less pool's jobs on whichever thread calls `run_until_idle`, in an
+// order that only depends on the seed and the order jobs were submitted in.
+pub(crate) struct Scheduler {
+    rng: Mutex<Rng>,
+    // Jobs taken off the lanes but not run yet, so each pick can choose among all
+    // of them rather than just the oldest.
+    ready: Mutex<[VecDeque<Task>; Priority::COUNT]>,
+    pub(crate) counters: WorkerCounters,
+}
+
+impl Scheduler {
+    pub(crate) fn new(seed: u64) -> Scheduler {
+        Scheduler {
+            rng: Mutex::new(Rng::new(seed)),
+            ready: Mutex::new(Default::default()),
+            counters: WorkerCounters::new(),
+        }
+    }
+
+    // Picks a random job among the highest priority ones that are ready.
+    pub(crate) fn next(&self, queue: &Queue) -> Option<Task> {
+        let mut ready = self.ready.lock().unwrap();
+        for (lane, tasks) in ready.iter_mut().enumerate() {
+            tasks.extend(iter::from_fn(|| queue.try_recv(lane)));
+        }
+        let tasks = ready.iter_mut().find(|tasks| !tasks.is_empty())?;
+        let pick = self.rng.lock().unwrap().below(tasks.len());
+        tasks.remove(pick)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ready.lock().unwrap().iter().map(VecDeque::len).sum()
+    }
+
+    pub(crate) fn drain(&self) -> usize {
+        let mut ready = self.ready.lock().unwrap();
+        ready.iter_mut().map(|tasks| tasks.drain(..).count()).sum()
+    }
+}
+
+// xorshift64*, plenty for shuffling jobs and stable across platforms and releases.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // Zero is the one state xorshift never leaves.
+        Rng(if seed == 0 { 0x9e3779b97f4a7c15 } else { seed })
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let x = self.0.wrapping_mul(0x2545f4914f6cdd1d);
+        (x % n as u64) as usize
+    }
+}
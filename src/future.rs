@@ -0,0 +1,180 @@
+use crossbeam::{channel, sync::ShardedLock};
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use crate::{
+    panic_message, queue::Lanes, CancellationToken, ExecuteError, JobHandle, JoinError, Priority,
+    Shared, Submit, Task, ThreadPool,
+};
+
+// Waiting for a wake.
+const IDLE: u8 = 0;
+// Queued on the pool.
+const SCHEDULED: u8 = 1;
+const RUNNING: u8 = 2;
+// Woken while running, polled again once the current poll returns.
+const NOTIFIED: u8 = 3;
+const DONE: u8 = 4;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+struct FutureTask<T> {
+    state: AtomicU8,
+    future: Mutex<Option<BoxFuture<T>>>,
+    tx: Mutex<Option<channel::Sender<Result<T, JoinError>>>>,
+    token: CancellationToken,
+    shared: Arc<Shared>,
+    // Weak so a future that is never woken again doesn't hold the pool open.
+    lanes: Weak<ShardedLock<Option<Lanes>>>,
+}
+
+impl<T: Send + 'static> FutureTask<T> {
+    fn task(self: Arc<Self>) -> Task {
+        let timed = self.shared.timed.load(Ordering::Relaxed);
+        Task::new(Box::new(move || self.run()), timed)
+    }
+
+    fn run(self: Arc<Self>) {
+        self.state.store(RUNNING, Ordering::SeqCst);
+        if self.token.is_cancelled() {
+            self.finish(None);
+            return;
+        }
+        let mut future = self.future.lock().unwrap();
+        let Some(fut) = future.as_mut() else {
+            return;
+        };
+        let waker = Waker::from(Arc::clone(&self));
+        let mut cx = Context::from_waker(&waker);
+        match panic::catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(&mut cx))) {
+            Ok(Poll::Ready(value)) => {
+                *future = None;
+                drop(future);
+                self.finish(Some(Ok(value)));
+            }
+            Ok(Poll::Pending) => {
+                drop(future);
+                let idle =
+                    self.state
+                        .compare_exchange(RUNNING, IDLE, Ordering::SeqCst, Ordering::SeqCst);
+                // Woken during the poll, go to the back of the queue rather than
+                // polling again straight away.
+                if idle.is_err() {
+                    self.state.store(SCHEDULED, Ordering::SeqCst);
+                    self.schedule();
+                }
+            }
+            Err(payload) => {
+                *future = None;
+                drop(future);
+                let message = panic_message(payload.as_ref()).to_string();
+                self.finish(Some(Err(JoinError::Panicked(message))));
+                // Let the worker see the panic so it is logged and counted.
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+
+    // Drops the future and the result sender, without a result the handle reports
+    // the job as dropped or cancelled.
+    fn finish(&self, result: Option<Result<T, JoinError>>) {
+        self.state.store(DONE, Ordering::SeqCst);
+        *self.future.lock().unwrap() = None;
+        let tx = self.tx.lock().unwrap().take();
+        if let (Some(tx), Some(result)) = (tx, result) {
+            let _ = tx.send(result);
+        }
+    }
+
+    fn schedule(self: Arc<Self>) {
+        let task = match self.shared.queue.push_local(Arc::clone(&self).task()) {
+            Ok(()) => return,
+            Err(task) => task,
+        };
+        let lanes = self
+            .lanes
+            .upgrade()
+            .and_then(|lanes| lanes.read().unwrap().clone());
+        let sent = lanes.is_some_and(|lanes| lanes.send(Priority::Normal, task).is_ok());
+        if !sent {
+            // The pool is gone, nothing will poll the future again.
+            self.finish(None);
+        }
+    }
+}
+
+impl<T: Send + 'static> Wake for FutureTask<T> {
+    fn wake(self: Arc<Self>) {
+        loop {
+            let state = self.state.load(Ordering::SeqCst);
+            let next = match state {
+                IDLE => SCHEDULED,
+                RUNNING => NOTIFIED,
+                _ => return,
+            };
+            if self
+                .state
+                .compare_exchange(state, next, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                if next == SCHEDULED {
+                    self.schedule();
+                }
+                return;
+            }
+        }
+    }
+}
+
+impl ThreadPool {
+    // Polls `future` on the pool's workers, re-queueing it each time it is woken.
+    pub fn spawn_future<F, T>(&self, future: F) -> Result<JobHandle<T>, ExecuteError>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = channel::bounded(1);
+        let token = CancellationToken::new();
+        let task = Arc::new(FutureTask {
+            state: AtomicU8::new(SCHEDULED),
+            future: Mutex::new(Some(Box::pin(future))),
+            tx: Mutex::new(Some(tx)),
+            token: token.clone(),
+            shared: Arc::clone(&self.shared),
+            lanes: Arc::downgrade(&self.sender),
+        });
+        self.submit(task.task(), Priority::Normal, Submit::Block)?;
+        Ok(JobHandle::new(rx, token))
+    }
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Runs `future` to completion on the calling thread. Called from a worker it
+// holds that worker until the future is done.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+            return value;
+        }
+        thread::park();
+    }
+}
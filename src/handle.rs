@@ -0,0 +1,80 @@
+use crossbeam::channel;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::CancellationToken;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum JoinError {
+    #[error("Job panicked: {0}")]
+    Panicked(String),
+    #[error("Timed out waiting for job to finish")]
+    Timeout,
+    #[error("Job was dropped before it finished")]
+    Dropped,
+    #[error("Job was cancelled before it started")]
+    Cancelled,
+}
+
+pub struct JobHandle<T> {
+    rx: channel::Receiver<Result<T, JoinError>>,
+    token: CancellationToken,
+}
+
+impl<T> JobHandle<T> {
+    pub(crate) fn new(
+        rx: channel::Receiver<Result<T, JoinError>>,
+        token: CancellationToken,
+    ) -> JobHandle<T> {
+        JobHandle { rx, token }
+    }
+
+    // Keeps the job from starting if it is still queued, and signals it through its
+    // token if it is already running.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    fn dropped(&self) -> JoinError {
+        if self.token.is_cancelled() {
+            JoinError::Cancelled
+        } else {
+            JoinError::Dropped
+        }
+    }
+
+    // Agrees with `try_join`: a job that was cancelled or dropped has finished
+    // too. A receive is ready once a result is waiting or the sender is gone.
+    pub fn is_finished(&self) -> bool {
+        let mut select = channel::Select::new();
+        select.recv(&self.rx);
+        select.try_ready().is_ok()
+    }
+
+    pub fn join(self) -> Result<T, JoinError> {
+        self.rx.recv().unwrap_or_else(|_| Err(self.dropped()))
+    }
+
+    // Returns None while the job is still queued or running.
+    pub fn try_join(&self) -> Option<Result<T, JoinError>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(channel::TryRecvError::Empty) => None,
+            Err(channel::TryRecvError::Disconnected) => Some(Err(self.dropped())),
+        }
+    }
+
+    pub fn join_timeout(&self, timeout: Duration) -> Result<T, JoinError> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(channel::RecvTimeoutError::Timeout) => Err(JoinError::Timeout),
+            Err(channel::RecvTimeoutError::Disconnected) => Err(self.dropped()),
+        }
+    }
+}
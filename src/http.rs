@@ -0,0 +1,10 @@
+mod files;
+pub mod path;
+mod request;
+mod response;
+mod router;
+
+pub use files::StaticFiles;
+pub use request::{Headers, Limits, Method, ParseError, Request, Version};
+pub use response::{Body, Response, Status};
+pub use router::{Params, Router};
@@ -0,0 +1,120 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{error, warn};
+
+use super::{
+    path::{self, PathError},
+    Response, Status,
+};
+
+// Serves files from a document root. A request for "/foo" is answered with
+// "foo", "foo.html" or "foo/index.html", whichever exists first, and anything else
+// with the root's "404.html". Paths that climb out of the root, directly or
+// through a symlink, are refused with 403.
+pub struct StaticFiles {
+    root: PathBuf,
+    // None if the root didn't exist at startup, then nothing is served.
+    canonical: Option<PathBuf>,
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> StaticFiles {
+        let root = root.into();
+        let canonical = match fs::canonicalize(&root) {
+            Ok(canonical) => Some(canonical),
+            Err(e) => {
+                error!("Failed to open document root {}: {e}", root.display());
+                None
+            }
+        };
+        StaticFiles { root, canonical }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // `path` is the raw request path, still percent-encoded.
+    pub fn resolve(&self, path: &str) -> Result<Option<PathBuf>, PathError> {
+        let relative = path::sanitize(path)?;
+        let Some(root) = &self.canonical else {
+            return Ok(None);
+        };
+        let base = root.join(&relative);
+        let index = base.join("index.html");
+        let candidates = if relative.as_os_str().is_empty() {
+            vec![index]
+        } else {
+            let mut html = base.clone().into_os_string();
+            html.push(".html");
+            vec![base, PathBuf::from(html), index]
+        };
+        for candidate in candidates {
+            if let Some(file) = path::confine(root, &candidate)? {
+                if file.is_file() {
+                    return Ok(Some(file));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn serve(&self, path: &str) -> Response {
+        match self.resolve(path) {
+            Ok(Some(file)) => self.respond(Status::Ok, &file),
+            Ok(None) => self.not_found(),
+            Err(e) => {
+                warn!("Refused {path:?}: {e}");
+                Response::new(e.status())
+            }
+        }
+    }
+
+    // Responds with a file from the root, e.g. `file(Status::Ok, "home.html")`.
+    pub fn file(&self, status: Status, name: &str) -> Response {
+        self.respond(status, &self.root.join(name))
+    }
+
+    pub fn not_found(&self) -> Response {
+        let page = self.root.join("404.html");
+        if page.is_file() {
+            self.respond(Status::NotFound, &page)
+        } else {
+            Response::new(Status::NotFound)
+        }
+    }
+
+    fn respond(&self, status: Status, file: &Path) -> Response {
+        Response::new(status)
+            .header("Content-Type", content_type(file))
+            .file(file)
+            .unwrap_or_else(|e| {
+                error!("Failed to read {}: {e}", file.display());
+                Response::new(Status::InternalServerError)
+            })
+    }
+}
+
+fn content_type(file: &Path) -> &'static str {
+    let extension = file.extension().and_then(OsStr::to_str).unwrap_or_default();
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use super::Status;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PathError {
+    #[error("Malformed percent-encoding in path")]
+    Encoding,
+    #[error("Path is not valid UTF-8")]
+    Utf8,
+    #[error("Path contains a NUL byte")]
+    Nul,
+    #[error("Path climbs above the document root")]
+    Traversal,
+    #[error("Path resolves outside the document root")]
+    Escape,
+    #[error("Failed to resolve path: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl PathError {
+    pub fn status(&self) -> Status {
+        match self {
+            PathError::Encoding | PathError::Utf8 | PathError::Nul => Status::BadRequest,
+            PathError::Traversal | PathError::Escape => Status::Forbidden,
+            PathError::Io(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// Percent-decodes a request path. "+" is left alone, it only means space in
+// query strings.
+pub fn decode(path: &str) -> Result<String, PathError> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            // `from_str_radix` alone would also take a sign, as in "%+1".
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .ok_or(PathError::Encoding)?;
+            let hex = std::str::from_utf8(hex).map_err(|_| PathError::Encoding)?;
+            let byte = u8::from_str_radix(hex, 16).map_err(|_| PathError::Encoding)?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    if decoded.contains(&0) {
+        return Err(PathError::Nul);
+    }
+    String::from_utf8(decoded).map_err(|_| PathError::Utf8)
+}
+
+// Turns a decoded path into a relative one made only of plain names. "." segments
+// and empty ones are dropped, ".." removes the one before it, and a ".." with
+// nothing left to remove is refused. Backslashes count as separators so they
+// can't smuggle a ".." past this on Windows.
+pub fn normalize(decoded: &str) -> Result<PathBuf, PathError> {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split(['/', '\\']) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop().ok_or(PathError::Traversal)?;
+            }
+            // A drive or stream prefix like "C:" would replace the root on Windows.
+            _ if cfg!(windows) && segment.contains(':') => return Err(PathError::Traversal),
+            _ => segments.push(segment),
+        }
+    }
+    Ok(segments.iter().collect())
+}
+
+// Decodes and normalizes a request path into one relative to the document root.
+pub fn sanitize(path: &str) -> Result<PathBuf, PathError> {
+    normalize(&decode(path)?)
+}
+
+// Resolves symlinks in `candidate` and checks the result is still under `root`,
+// which must already be canonical. Returns None if there is no such file, which
+// includes paths that go through a regular file, like "page.html/x", and names
+// too long for the filesystem.
+pub fn confine(root: &Path, candidate: &Path) -> Result<Option<PathBuf>, PathError> {
+    let resolved = match fs::canonicalize(candidate) {
+        Ok(resolved) => resolved,
+        Err(e) if is_missing(&e) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if !resolved.starts_with(root) {
+        return Err(PathError::Escape);
+    }
+    Ok(Some(resolved))
+}
+
+fn is_missing(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory | io::ErrorKind::InvalidFilename
+    )
+}
@@ -0,0 +1,596 @@
+use std::{
+    fmt,
+    io::{self, BufRead, Read},
+    str,
+};
+
+use thiserror::Error;
+
+use super::Status;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // Longest request line or header line accepted, without the line ending.
+    pub max_line: usize,
+    pub max_headers: usize,
+    pub max_body: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_line: 8 * 1024,
+            max_headers: 100,
+            max_body: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ParseError {
+    #[error("Connection closed before a request was sent")]
+    Closed,
+    #[error("Failed to read request: {0}")]
+    Io(#[from] io::Error),
+    #[error("Malformed request line")]
+    RequestLine,
+    #[error("Unsupported HTTP version {0:?}")]
+    Version(String),
+    #[error("Malformed header line")]
+    Header,
+    #[error("HTTP/1.1 request without a Host header")]
+    MissingHost,
+    #[error("Invalid Content-Length")]
+    ContentLength,
+    #[error("Malformed chunked body")]
+    Chunk,
+    #[error("Unsupported Transfer-Encoding {0:?}")]
+    TransferEncoding(String),
+    #[error("Request target longer than {0} bytes")]
+    UriTooLong(usize),
+    #[error("Header line longer than {0} bytes")]
+    HeaderTooLong(usize),
+    #[error("More than {0} headers")]
+    TooManyHeaders(usize),
+    #[error("Body longer than {0} bytes")]
+    BodyTooLarge(usize),
+}
+
+impl ParseError {
+    // The status to answer with, or None if the connection is gone.
+    pub fn status(&self) -> Option<Status> {
+        match self {
+            ParseError::Closed | ParseError::Io(_) => None,
+            ParseError::UriTooLong(_) => Some(Status::UriTooLong),
+            ParseError::HeaderTooLong(_) | ParseError::TooManyHeaders(_) => {
+                Some(Status::RequestHeaderFieldsTooLarge)
+            }
+            ParseError::BodyTooLarge(_) => Some(Status::PayloadTooLarge),
+            ParseError::TransferEncoding(_) => Some(Status::NotImplemented),
+            _ => Some(Status::BadRequest),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Options,
+    Patch,
+    Other(String),
+}
+
+impl Method {
+    fn parse(s: &str) -> Method {
+        match s {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "OPTIONS" => Method::Options,
+            "PATCH" => Method::Patch,
+            other => Method::Other(other.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+            Method::Patch => "PATCH",
+            Method::Other(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Version::Http10 => f.write_str("HTTP/1.0"),
+            Version::Http11 => f.write_str("HTTP/1.1"),
+        }
+    }
+}
+
+// Header names compare case-insensitively. Order and repeated headers are kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Headers {
+        Headers::default()
+    }
+
+    // The first value for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // Adds a value, keeping any already there.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    // Replaces every value for `name` with this one.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.remove(&name);
+        self.entries.push((name, value.into()));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: Method,
+    // As sent, e.g. "/search?q=rust".
+    pub target: String,
+    pub version: Version,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    // Reads one request, body included. Only Content-Length and chunked bodies are
+    // understood.
+    pub fn read_from<R: BufRead>(reader: &mut R, limits: &Limits) -> Result<Request, ParseError> {
+        let mut line = Vec::new();
+        // Clients may send blank lines between requests.
+        loop {
+            if !read_line(reader, &mut line, limits.max_line)? {
+                return Err(ParseError::Closed);
+            }
+            if !line.is_empty() {
+                break;
+            }
+        }
+        if line.len() > limits.max_line {
+            return Err(ParseError::UriTooLong(limits.max_line));
+        }
+        let (method, target, version) = parse_request_line(&line)?;
+
+        let mut headers = Headers::new();
+        loop {
+            if !read_line(reader, &mut line, limits.max_line)? {
+                return Err(ParseError::Header);
+            }
+            if line.len() > limits.max_line {
+                return Err(ParseError::HeaderTooLong(limits.max_line));
+            }
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == limits.max_headers {
+                return Err(ParseError::TooManyHeaders(limits.max_headers));
+            }
+            let (name, value) = parse_header(&line)?;
+            headers.append(name, value);
+        }
+        if version == Version::Http11 && !headers.contains("Host") {
+            return Err(ParseError::MissingHost);
+        }
+
+        let body = read_body(reader, &headers, limits)?;
+        Ok(Request {
+            method,
+            target,
+            version,
+            headers,
+            body,
+        })
+    }
+
+    // The target without its query string.
+    pub fn path(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map_or(&self.target, |(path, _)| path)
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        self.target.split_once('?').map(|(_, query)| query)
+    }
+
+    // Whether the client wants the connection kept open after the response.
+    pub fn keep_alive(&self) -> bool {
+        let connection = self.headers.get("Connection");
+        match self.version {
+            Version::Http11 => !connection.is_some_and(|c| c.eq_ignore_ascii_case("close")),
+            Version::Http10 => connection.is_some_and(|c| c.eq_ignore_ascii_case("keep-alive")),
+        }
+    }
+}
+
+// Reads up to and including "\n" into `line`, without the line ending. Returns
+// false at end of input. A line longer than `max` is cut off at `max + 1` bytes so
+// the caller can tell it was too long without buffering the whole thing.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut Vec<u8>, max: usize) -> io::Result<bool> {
+    line.clear();
+    // Room for the longest line plus "\r\n".
+    let n = reader
+        .by_ref()
+        .take(max as u64 + 2)
+        .read_until(b'\n', line)?;
+    if n == 0 {
+        return Ok(false);
+    }
+    if line.ends_with(b"\n") {
+        line.pop();
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+    } else if line.len() <= max {
+        // End of input in the middle of a line.
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    } else {
+        line.truncate(max + 1);
+    }
+    Ok(true)
+}
+
+fn parse_request_line(line: &[u8]) -> Result<(Method, String, Version), ParseError> {
+    let line = str::from_utf8(line).map_err(|_| ParseError::RequestLine)?;
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(ParseError::RequestLine);
+    };
+    if method.is_empty() || !method.bytes().all(is_token) || target.is_empty() {
+        return Err(ParseError::RequestLine);
+    }
+    let version = match version {
+        "HTTP/1.1" => Version::Http11,
+        "HTTP/1.0" => Version::Http10,
+        other => return Err(ParseError::Version(other.to_string())),
+    };
+    Ok((Method::parse(method), target.to_string(), version))
+}
+
+fn parse_header(line: &[u8]) -> Result<(String, String), ParseError> {
+    let line = str::from_utf8(line).map_err(|_| ParseError::Header)?;
+    let (name, value) = line.split_once(':').ok_or(ParseError::Header)?;
+    // Also rules out obsolete line folding, which starts with whitespace.
+    if name.is_empty() || !name.bytes().all(is_token) {
+        return Err(ParseError::Header);
+    }
+    Ok((
+        name.to_string(),
+        value.trim_matches([' ', '\t']).to_string(),
+    ))
+}
+
+fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+fn read_body<R: BufRead>(
+    reader: &mut R,
+    headers: &Headers,
+    limits: &Limits,
+) -> Result<Vec<u8>, ParseError> {
+    if let Some(encoding) = headers.get("Transfer-Encoding") {
+        if !encoding.eq_ignore_ascii_case("chunked") {
+            return Err(ParseError::TransferEncoding(encoding.to_string()));
+        }
+        return read_chunked(reader, limits);
+    }
+    let mut lengths = headers.get_all("Content-Length");
+    let length = match lengths.next() {
+        Some(length) => length,
+        None => return Ok(Vec::new()),
+    };
+    if lengths.any(|other| other != length) || !length.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseError::ContentLength);
+    }
+    let length: usize = length.parse().map_err(|_| ParseError::ContentLength)?;
+    if length > limits.max_body {
+        return Err(ParseError::BodyTooLarge(limits.max_body));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R, limits: &Limits) -> Result<Vec<u8>, ParseError> {
+    let mut body = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        if !read_line(reader, &mut line, limits.max_line)? || line.len() > limits.max_line {
+            return Err(ParseError::Chunk);
+        }
+        // Chunk extensions after ';' are ignored.
+        let size = line.split(|&b| b == b';').next().unwrap_or_default();
+        let size = str::from_utf8(size).map_err(|_| ParseError::Chunk)?.trim();
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseError::Chunk);
+        }
+        // Too many digits for a usize is too large a body either way.
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| ParseError::BodyTooLarge(limits.max_body))?;
+        if size == 0 {
+            break;
+        }
+        // `body` never exceeds the limit, so this can't underflow the way the sum
+        // could overflow.
+        if size > limits.max_body - body.len() {
+            return Err(ParseError::BodyTooLarge(limits.max_body));
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        if !read_line(reader, &mut line, limits.max_line)? || !line.is_empty() {
+            return Err(ParseError::Chunk);
+        }
+    }
+    // Trailers are read and dropped.
+    let mut trailers = 0;
+    loop {
+        if !read_line(reader, &mut line, limits.max_line)? {
+            return Err(ParseError::Chunk);
+        }
+        if line.is_empty() {
+            return Ok(body);
+        }
+        trailers += 1;
+        if line.len() > limits.max_line || trailers > limits.max_headers {
+            return Err(ParseError::Chunk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> Result<Request, ParseError> {
+        parse_with(raw, &Limits::default())
+    }
+
+    fn parse_with(raw: &str, limits: &Limits) -> Result<Request, ParseError> {
+        Request::read_from(&mut raw.as_bytes(), limits)
+    }
+
+    fn status(raw: &str) -> Option<Status> {
+        parse(raw).unwrap_err().status()
+    }
+
+    fn chunked(body: &str) -> String {
+        format!("POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n{body}")
+    }
+
+    #[test]
+    fn parses_a_request() {
+        let request = parse(
+            "\r\nGET /search?q=rust HTTP/1.1\r\nHost: a\r\nX-Tag: one\r\nx-tag:  two \r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.version, Version::Http11);
+        assert_eq!(request.path(), "/search");
+        assert_eq!(request.query(), Some("q=rust"));
+        assert_eq!(request.headers.get("HOST"), Some("a"));
+        let tags: Vec<_> = request.headers.get_all("X-Tag").collect();
+        assert_eq!(tags, ["one", "two"]);
+        assert!(request.body.is_empty());
+        assert!(request.keep_alive());
+    }
+
+    #[test]
+    fn bare_newlines_and_http10() {
+        let request = parse("BREW /pot HTTP/1.0\nConnection: keep-alive\n\n").unwrap();
+        assert_eq!(request.method, Method::Other("BREW".to_string()));
+        assert_eq!(request.version, Version::Http10);
+        assert!(request.keep_alive());
+    }
+
+    #[test]
+    fn closed_and_truncated_connections_get_no_response() {
+        assert!(matches!(parse(""), Err(ParseError::Closed)));
+        assert!(matches!(parse("\r\n\r\n"), Err(ParseError::Closed)));
+        assert!(matches!(parse("GET / HTTP/1.1"), Err(ParseError::Io(_))));
+        assert_eq!(status("GET / HTTP/1.1"), None);
+        let truncated = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 10\r\n\r\nshort";
+        assert_eq!(status(truncated), None);
+    }
+
+    #[test]
+    fn malformed_requests_are_bad_requests() {
+        for raw in [
+            "GET  / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1 extra\r\nHost: a\r\n\r\n",
+            "G(T / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/2.0\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nNo colon\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\n folded\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\n: empty\r\n\r\n",
+        ] {
+            assert_eq!(status(raw), Some(Status::BadRequest), "{raw:?}");
+        }
+        assert!(matches!(
+            parse("GET / HTTP/1.1\r\n\r\n"),
+            Err(ParseError::MissingHost)
+        ));
+    }
+
+    #[test]
+    fn line_and_header_limits() {
+        let limits = Limits {
+            max_line: 32,
+            max_headers: 2,
+            max_body: 16,
+        };
+        let target = "a".repeat(32 - "GET / HTTP/1.1".len());
+        let fits = format!("GET /{target} HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert!(parse_with(&fits, &limits).is_ok());
+        let long = format!("GET /{target}a HTTP/1.1\r\nHost: a\r\n\r\n");
+        let error = parse_with(&long, &limits).unwrap_err();
+        assert!(matches!(error, ParseError::UriTooLong(32)));
+        assert_eq!(error.status(), Some(Status::UriTooLong));
+
+        let header = format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", "a".repeat(32));
+        let error = parse_with(&header, &limits).unwrap_err();
+        assert!(matches!(error, ParseError::HeaderTooLong(32)));
+        assert_eq!(error.status(), Some(Status::RequestHeaderFieldsTooLarge));
+
+        let two = "GET / HTTP/1.1\r\nHost: a\r\nA: 1\r\n\r\n";
+        assert!(parse_with(two, &limits).is_ok());
+        let three = "GET / HTTP/1.1\r\nHost: a\r\nA: 1\r\nB: 2\r\n\r\n";
+        let error = parse_with(three, &limits).unwrap_err();
+        assert!(matches!(error, ParseError::TooManyHeaders(2)));
+        assert_eq!(error.status(), Some(Status::RequestHeaderFieldsTooLarge));
+    }
+
+    #[test]
+    fn content_length() {
+        let request =
+            parse("POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello, extra").unwrap();
+        assert_eq!(request.body, b"hello");
+        let repeated =
+            "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 2\r\nContent-Length: 2\r\n\r\nhi";
+        assert_eq!(parse(repeated).unwrap().body, b"hi");
+        for length in ["2\r\nContent-Length: 3", "+2", "-2", "0x2", "2 2", ""] {
+            let raw = format!("POST / HTTP/1.1\r\nHost: a\r\nContent-Length: {length}\r\n\r\nhi!");
+            assert!(
+                matches!(parse(&raw), Err(ParseError::ContentLength)),
+                "{length:?}"
+            );
+        }
+        let huge = format!(
+            "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: {}0\r\n\r\n",
+            usize::MAX
+        );
+        assert!(matches!(parse(&huge), Err(ParseError::ContentLength)));
+        let large = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 1048577\r\n\r\n";
+        assert_eq!(status(large), Some(Status::PayloadTooLarge));
+    }
+
+    #[test]
+    fn chunked_bodies() {
+        let request = parse(&chunked(
+            "4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\nExpires: never\r\n\r\n",
+        ))
+        .unwrap();
+        assert_eq!(request.body, b"Wikipedia");
+        // Transfer-Encoding wins over Content-Length.
+        let both = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 100\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n";
+        assert_eq!(parse(both).unwrap().body, b"hi");
+        let gzip = "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: gzip\r\n\r\n";
+        assert_eq!(status(gzip), Some(Status::NotImplemented));
+    }
+
+    #[test]
+    fn malformed_chunks() {
+        for body in [
+            "zz\r\n",
+            "\r\n",
+            "+2\r\nhi\r\n0\r\n\r\n",
+            "-2\r\nhi\r\n0\r\n\r\n",
+            "2\r\nhiX\r\n0\r\n\r\n",
+            "2\r\nhi\r\n0\r\n",
+        ] {
+            assert!(
+                matches!(parse(&chunked(body)), Err(ParseError::Chunk)),
+                "{body:?}"
+            );
+        }
+        let trailers = "X: 1\r\n".repeat(101);
+        let raw = chunked(&format!("0\r\n{trailers}\r\n"));
+        assert!(matches!(parse(&raw), Err(ParseError::Chunk)));
+        assert_eq!(status(&chunked("5\r\nhi")), None);
+    }
+
+    #[test]
+    fn oversized_chunks() {
+        let limits = Limits {
+            max_body: 4,
+            ..Limits::default()
+        };
+        for body in [
+            "1\r\na\r\nffffffffffffffff\r\n",
+            "ffffffffffffffff\r\n",
+            "10000000000000000\r\n",
+            "3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n",
+        ] {
+            let error = parse_with(&chunked(body), &limits).unwrap_err();
+            assert!(matches!(error, ParseError::BodyTooLarge(4)), "{body:?}");
+            assert_eq!(error.status(), Some(Status::PayloadTooLarge));
+        }
+        let fits = chunked("3\r\nabc\r\n1\r\nd\r\n0\r\n\r\n");
+        assert_eq!(parse_with(&fits, &limits).unwrap().body, b"abcd");
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::Headers;
+
+const SERVER: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Status {
+    Ok,
+    Created,
+    NoContent,
+    MovedPermanently,
+    Found,
+    NotModified,
+    BadRequest,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    PayloadTooLarge,
+    UriTooLong,
+    RequestHeaderFieldsTooLarge,
+    InternalServerError,
+    NotImplemented,
+    ServiceUnavailable,
+}
+
+impl Status {
+    pub fn code(self) -> u16 {
+        match self {
+            Status::Ok => 200,
+            Status::Created => 201,
+            Status::NoContent => 204,
+            Status::MovedPermanently => 301,
+            Status::Found => 302,
+            Status::NotModified => 304,
+            Status::BadRequest => 400,
+            Status::Forbidden => 403,
+            Status::NotFound => 404,
+            Status::MethodNotAllowed => 405,
+            Status::PayloadTooLarge => 413,
+            Status::UriTooLong => 414,
+            Status::RequestHeaderFieldsTooLarge => 431,
+            Status::InternalServerError => 500,
+            Status::NotImplemented => 501,
+            Status::ServiceUnavailable => 503,
+        }
+    }
+
+    pub fn reason(self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Created => "CREATED",
+            Status::NoContent => "NO CONTENT",
+            Status::MovedPermanently => "MOVED PERMANENTLY",
+            Status::Found => "FOUND",
+            Status::NotModified => "NOT MODIFIED",
+            Status::BadRequest => "BAD REQUEST",
+            Status::Forbidden => "FORBIDDEN",
+            Status::NotFound => "NOT FOUND",
+            Status::MethodNotAllowed => "METHOD NOT ALLOWED",
+            Status::PayloadTooLarge => "PAYLOAD TOO LARGE",
+            Status::UriTooLong => "URI TOO LONG",
+            Status::RequestHeaderFieldsTooLarge => "REQUEST HEADER FIELDS TOO LARGE",
+            Status::InternalServerError => "INTERNAL SERVER ERROR",
+            Status::NotImplemented => "NOT IMPLEMENTED",
+            Status::ServiceUnavailable => "SERVICE UNAVAILABLE",
+        }
+    }
+
+    // 1xx, 204 and 304 responses never have a body.
+    fn allows_body(self) -> bool {
+        !matches!(self, Status::NoContent | Status::NotModified)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason())
+    }
+}
+
+pub enum Body {
+    Empty,
+    Bytes(Vec<u8>),
+    File { file: File, len: u64 },
+    // Sent with chunked encoding since the length isn't known up front.
+    Stream(Box<dyn Read + Send>),
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Empty => f.write_str("Empty"),
+            Body::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Body::File { len, .. } => write!(f, "File({len} bytes)"),
+            Body::Stream(_) => f.write_str("Stream"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: Status,
+    pub headers: Headers,
+    pub body: Body,
+    // Write the head only, as the answer to a HEAD request.
+    pub head_only: bool,
+}
+
+impl Response {
+    pub fn new(status: Status) -> Response {
+        Response {
+            status,
+            headers: Headers::new(),
+            body: Body::Empty,
+            head_only: false,
+        }
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Response {
+        self.headers.set(name, value);
+        self
+    }
+
+    pub fn bytes(mut self, bytes: impl Into<Vec<u8>>) -> Response {
+        self.body = Body::Bytes(bytes.into());
+        self
+    }
+
+    pub fn file(mut self, path: impl AsRef<Path>) -> io::Result<Response> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        self.body = Body::File { file, len };
+        Ok(self)
+    }
+
+    pub fn stream(mut self, reader: impl Read + Send + 'static) -> Response {
+        self.body = Body::Stream(Box::new(reader));
+        self
+    }
+
+    // Writes the status line, headers and body. Date, Server and the framing
+    // headers are added unless already set.
+    pub fn write_to<W: Write>(self, out: &mut W) -> io::Result<()> {
+        let Response {
+            status,
+            mut headers,
+            body,
+            head_only,
+        } = self;
+        let body = if status.allows_body() {
+            body
+        } else {
+            Body::Empty
+        };
+        if !headers.contains("Date") {
+            headers.set("Date", http_date(SystemTime::now()));
+        }
+        if !headers.contains("Server") {
+            headers.set("Server", SERVER);
+        }
+        headers.remove("Transfer-Encoding");
+        match &body {
+            Body::Empty if !status.allows_body() => headers.remove("Content-Length"),
+            Body::Empty => headers.set("Content-Length", "0"),
+            Body::Bytes(bytes) => headers.set("Content-Length", bytes.len().to_string()),
+            Body::File { len, .. } => headers.set("Content-Length", len.to_string()),
+            Body::Stream(_) => {
+                headers.remove("Content-Length");
+                headers.set("Transfer-Encoding", "chunked");
+            }
+        }
+
+        let mut head = Vec::with_capacity(256);
+        write!(head, "HTTP/1.1 {status}\r\n")?;
+        for (name, value) in headers.iter() {
+            write!(head, "{name}: {value}\r\n")?;
+        }
+        head.extend_from_slice(b"\r\n");
+        if head_only {
+            return out.write_all(&head);
+        }
+        match body {
+            Body::Empty => out.write_all(&head),
+            Body::Bytes(bytes) => {
+                head.extend_from_slice(&bytes);
+                out.write_all(&head)
+            }
+            Body::File { file, len } => {
+                out.write_all(&head)?;
+                let copied = io::copy(&mut file.take(len), out)?;
+                if copied < len {
+                    // The headers already promised `len` bytes.
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                Ok(())
+            }
+            Body::Stream(mut reader) => {
+                out.write_all(&head)?;
+                let mut buf = vec![0; 8 * 1024];
+                loop {
+                    let n = reader.read(&mut buf)?;
+                    if n == 0 {
+                        return out.write_all(b"0\r\n\r\n");
+                    }
+                    write!(out, "{n:x}\r\n")?;
+                    out.write_all(&buf[..n])?;
+                    out.write_all(b"\r\n")?;
+                }
+            }
+        }
+    }
+}
+
+// IMF-fixdate, e.g. "Sun, 06 Nov 1994 08:49:37 GMT".
+fn http_date(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let days = secs / 86_400;
+    let secs = secs % 86_400;
+    // Civil date from days since 1970-01-01, after Howard Hinnant's days_from_civil.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process, time::Duration};
+
+    fn written(response: Response) -> String {
+        let mut out = Vec::new();
+        response.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    // Splits the output into its header lines and body.
+    fn parts(output: &str) -> (Vec<&str>, &str) {
+        let (head, body) = output.split_once("\r\n\r\n").unwrap();
+        (head.split("\r\n").collect(), body)
+    }
+
+    fn header<'a>(lines: &[&'a str], name: &str) -> Option<&'a str> {
+        lines.iter().find_map(|line| {
+            let (n, v) = line.split_once(": ")?;
+            n.eq_ignore_ascii_case(name).then_some(v)
+        })
+    }
+
+    #[test]
+    fn content_length_for_sized_bodies() {
+        let output = written(Response::new(Status::Ok));
+        let (lines, body) = parts(&output);
+        assert_eq!(lines[0], "HTTP/1.1 200 OK");
+        assert_eq!(header(&lines, "Content-Length"), Some("0"));
+        assert_eq!(body, "");
+
+        let output = written(Response::new(Status::Ok).bytes("hello"));
+        let (lines, body) = parts(&output);
+        assert_eq!(header(&lines, "Content-Length"), Some("5"));
+        assert_eq!(header(&lines, "Transfer-Encoding"), None);
+        assert_eq!(body, "hello");
+
+        let path = std::env::temp_dir().join(format!("response-file-{}", process::id()));
+        fs::write(&path, "file body").unwrap();
+        let response = Response::new(Status::Ok).file(&path).unwrap();
+        let output = written(response);
+        fs::remove_file(&path).unwrap();
+        let (lines, body) = parts(&output);
+        assert_eq!(header(&lines, "Content-Length"), Some("9"));
+        assert_eq!(body, "file body");
+    }
+
+    #[test]
+    fn no_body_or_length_for_204_and_304() {
+        for status in [Status::NoContent, Status::NotModified] {
+            let response = Response::new(status)
+                .header("Content-Length", "5")
+                .bytes("hello");
+            let output = written(response);
+            let (lines, body) = parts(&output);
+            assert_eq!(header(&lines, "Content-Length"), None, "{status}");
+            assert_eq!(header(&lines, "Transfer-Encoding"), None, "{status}");
+            assert_eq!(body, "", "{status}");
+        }
+    }
+
+    #[test]
+    fn head_only_writes_the_headers() {
+        let mut response = Response::new(Status::Ok).bytes("hello");
+        response.head_only = true;
+        let output = written(response);
+        let (lines, body) = parts(&output);
+        assert_eq!(header(&lines, "Content-Length"), Some("5"));
+        assert_eq!(body, "");
+
+        let mut response = Response::new(Status::Ok).stream(&b"hello"[..]);
+        response.head_only = true;
+        let output = written(response);
+        let (lines, body) = parts(&output);
+        assert_eq!(header(&lines, "Transfer-Encoding"), Some("chunked"));
+        assert_eq!(body, "");
+    }
+
+    #[test]
+    fn streams_are_chunked() {
+        let reader = b"hello".chain(&b" world"[..]);
+        let response = Response::new(Status::Ok)
+            .header("Content-Length", "11")
+            .stream(reader);
+        let output = written(response);
+        let (lines, body) = parts(&output);
+        assert_eq!(header(&lines, "Transfer-Encoding"), Some("chunked"));
+        assert_eq!(header(&lines, "Content-Length"), None);
+        assert_eq!(body, "5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n");
+
+        let response = Response::new(Status::Ok).stream(io::empty());
+        let output = written(response);
+        let (_, body) = parts(&output);
+        assert_eq!(body, "0\r\n\r\n");
+    }
+
+    #[test]
+    fn date_and_server_are_defaulted() {
+        let output = written(Response::new(Status::Ok));
+        let (lines, _) = parts(&output);
+        assert!(header(&lines, "Date").is_some_and(|date| date.ends_with(" GMT")));
+        assert_eq!(header(&lines, "Server"), Some(SERVER));
+
+        let response = Response::new(Status::Ok)
+            .header("Date", "yesterday")
+            .header("Server", "custom");
+        let output = written(response);
+        let (lines, _) = parts(&output);
+        assert_eq!(header(&lines, "Date"), Some("yesterday"));
+        assert_eq!(header(&lines, "Server"), Some("custom"));
+        assert_eq!(lines.iter().filter(|l| l.starts_with("Date")).count(), 1);
+    }
+
+    #[test]
+    fn http_dates() {
+        let date = |secs| http_date(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(date(784_111_777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(date(951_782_400), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(date(4_133_980_799), "Fri, 31 Dec 2100 23:59:59 GMT");
+    }
+}
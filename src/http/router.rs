@@ -0,0 +1,338 @@
+use super::{Method, Request, Response, Status};
+
+type Handler = Box<dyn Fn(&Request, &Params) -> Response + Send + Sync>;
+
+// Values captured by `:name` and `*name` segments of the matched pattern.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    entries: Vec<(String, String)>,
+}
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+enum Segment {
+    Literal(String),
+    // Matches one non-empty segment.
+    Param(String),
+    // Matches the rest of the path, possibly empty. Always last.
+    Rest(String),
+}
+
+struct Route {
+    method: Method,
+    pattern: Vec<Segment>,
+    handler: Handler,
+}
+
+impl Route {
+    fn matches(&self, path: &str) -> Option<Params> {
+        let path = path.strip_prefix('/')?;
+        let parts: Vec<&str> = if path.is_empty() {
+            Vec::new()
+        } else {
+            path.split('/').collect()
+        };
+        let mut params = Params::default();
+        for (i, segment) in self.pattern.iter().enumerate() {
+            match segment {
+                Segment::Literal(literal) if parts.get(i) == Some(&literal.as_str()) => {}
+                Segment::Param(name) => match parts.get(i) {
+                    Some(part) if !part.is_empty() => {
+                        params.entries.push((name.clone(), part.to_string()));
+                    }
+                    _ => return None,
+                },
+                Segment::Rest(name) => {
+                    params.entries.push((name.clone(), parts[i..].join("/")));
+                    return Some(params);
+                }
+                Segment::Literal(_) => return None,
+            }
+        }
+        (parts.len() == self.pattern.len()).then_some(params)
+    }
+}
+
+// Dispatches requests to handlers by method and path pattern, e.g. "/users/:id" or
+// "/static/*path". Routes are tried in the order they were added. HEAD requests
+// go to the GET handler with the body left off.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    not_found: Option<Handler>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    pub fn route<F>(mut self, method: Method, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            pattern: parse_pattern(pattern),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn get<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    pub fn post<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    // Answers requests no route matches. Defaults to an empty 404.
+    pub fn not_found<F>(mut self, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.not_found = Some(Box::new(handler));
+        self
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        let path = request.path();
+        let head = request.method == Method::Head;
+        let mut allowed: Vec<&Method> = Vec::new();
+        for route in &self.routes {
+            let Some(params) = route.matches(path) else {
+                continue;
+            };
+            if route.method == request.method {
+                return (route.handler)(request, &params);
+            }
+            if head && route.method == Method::Get {
+                let mut response = (route.handler)(request, &params);
+                response.head_only = true;
+                return response;
+            }
+            if !allowed.contains(&&route.method) {
+                allowed.push(&route.method);
+            }
+        }
+        if allowed.is_empty() {
+            return match &self.not_found {
+                Some(not_found) => (not_found)(request, &Params::default()),
+                None => Response::new(Status::NotFound),
+            };
+        }
+        if allowed.contains(&&Method::Get) && !allowed.contains(&&Method::Head) {
+            allowed.push(&Method::Head);
+        }
+        let allow: Vec<&str> = allowed.iter().map(|method| method.as_str()).collect();
+        Response::new(Status::MethodNotAllowed).header("Allow", allow.join(", "))
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    let segments: Vec<&str> = pattern.split('/').collect();
+    let last = segments.len() - 1;
+    segments
+        .into_iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            if let Some(name) = segment.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = segment.strip_prefix('*') {
+                assert!(
+                    i == last,
+                    "{segment:?} must be the last segment of {pattern:?}"
+                );
+                Segment::Rest(name.to_string())
+            } else {
+                Segment::Literal(segment.to_string())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Headers, Version};
+
+    fn request(method: Method, target: &str) -> Request {
+        Request {
+            method,
+            target: target.to_string(),
+            version: Version::Http11,
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    // Answers with the route's name and the captured params, e.g. "user id=7".
+    fn echo(name: &'static str) -> impl Fn(&Request, &Params) -> Response {
+        move |_, params| {
+            let params: Vec<String> = params
+                .entries
+                .iter()
+                .map(|(n, v)| format!("{n}={v}"))
+                .collect();
+            let route = format!("{name} {}", params.join(" "));
+            Response::new(Status::Ok).header("X-Route", route.trim_end())
+        }
+    }
+
+    fn route(router: &Router, method: Method, target: &str) -> Option<String> {
+        let response = router.handle(&request(method, target));
+        response.headers.get("X-Route").map(str::to_string)
+    }
+
+    fn router() -> Router {
+        Router::new()
+            .get("/", echo("root"))
+            .get("/users", echo("users"))
+            .get("/users/:id", echo("user"))
+            .post("/users/:id", echo("update"))
+            .get("/users/:id/posts/:post", echo("post"))
+            .get("/static/*path", echo("static"))
+    }
+
+    #[test]
+    fn literals_and_params() {
+        let router = router();
+        let get = |target| route(&router, Method::Get, target);
+        assert_eq!(get("/").as_deref(), Some("root"));
+        assert_eq!(get("/users").as_deref(), Some("users"));
+        assert_eq!(get("/users/7").as_deref(), Some("user id=7"));
+        assert_eq!(get("/users/7?x=1").as_deref(), Some("user id=7"));
+        assert_eq!(
+            get("/users/7/posts/hi").as_deref(),
+            Some("post id=7 post=hi")
+        );
+        assert_eq!(get("/users/7/posts"), None);
+        assert_eq!(get("/nope"), None);
+        assert_eq!(get("users"), None);
+    }
+
+    #[test]
+    fn params_reject_empty_segments() {
+        let router = router();
+        let get = |target| route(&router, Method::Get, target);
+        assert_eq!(get("/users/"), None);
+        assert_eq!(get("/users//posts/1"), None);
+        assert_eq!(get("/users/7/posts/"), None);
+    }
+
+    #[test]
+    fn trailing_slashes_are_distinct() {
+        let router = Router::new()
+            .get("/docs", echo("docs"))
+            .get("/docs/", echo("docs index"));
+        assert_eq!(
+            route(&router, Method::Get, "/docs").as_deref(),
+            Some("docs")
+        );
+        assert_eq!(
+            route(&router, Method::Get, "/docs/").as_deref(),
+            Some("docs index")
+        );
+        assert_eq!(route(&router, Method::Get, "/docs//"), None);
+    }
+
+    #[test]
+    fn rest_captures_the_remainder() {
+        let router = router();
+        let get = |target| route(&router, Method::Get, target);
+        assert_eq!(get("/static").as_deref(), Some("static path="));
+        assert_eq!(get("/static/").as_deref(), Some("static path="));
+        assert_eq!(
+            get("/static/css/site.css").as_deref(),
+            Some("static path=css/site.css")
+        );
+        assert_eq!(get("/static/a/").as_deref(), Some("static path=a/"));
+
+        let root = Router::new().get("/*path", echo("any"));
+        assert_eq!(route(&root, Method::Get, "/").as_deref(), Some("any path="));
+        assert_eq!(
+            route(&root, Method::Get, "/a/b").as_deref(),
+            Some("any path=a/b")
+        );
+    }
+
+    #[test]
+    fn first_matching_route_wins() {
+        let router = Router::new()
+            .get("/users/me", echo("me"))
+            .get("/users/:id", echo("user"));
+        assert_eq!(
+            route(&router, Method::Get, "/users/me").as_deref(),
+            Some("me")
+        );
+        assert_eq!(
+            route(&router, Method::Get, "/users/you").as_deref(),
+            Some("user id=you")
+        );
+    }
+
+    #[test]
+    fn head_falls_back_to_get() {
+        let response = router().handle(&request(Method::Head, "/users/7"));
+        assert_eq!(response.headers.get("X-Route"), Some("user id=7"));
+        assert!(response.head_only);
+
+        let explicit = Router::new()
+            .route(Method::Head, "/", echo("head"))
+            .get("/", echo("get"));
+        let response = explicit.handle(&request(Method::Head, "/"));
+        assert_eq!(response.headers.get("X-Route"), Some("head"));
+        assert!(!response.head_only);
+    }
+
+    #[test]
+    fn wrong_method_lists_allowed_ones() {
+        let router = router();
+        let response = router.handle(&request(Method::Delete, "/users/7"));
+        assert_eq!(response.status, Status::MethodNotAllowed);
+        assert_eq!(response.headers.get("Allow"), Some("GET, POST, HEAD"));
+
+        let response = router.handle(&request(Method::Post, "/users"));
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD"));
+
+        let post_only = Router::new().post("/form", echo("form"));
+        let response = post_only.handle(&request(Method::Get, "/form"));
+        assert_eq!(response.status, Status::MethodNotAllowed);
+        assert_eq!(response.headers.get("Allow"), Some("POST"));
+    }
+
+    #[test]
+    fn not_found() {
+        let response = router().handle(&request(Method::Delete, "/nope"));
+        assert_eq!(response.status, Status::NotFound);
+        assert!(!response.headers.contains("Allow"));
+
+        let custom = Router::new().not_found(echo("missing"));
+        let response = custom.handle(&request(Method::Get, "/nope"));
+        assert_eq!(response.headers.get("X-Route"), Some("missing"));
+    }
+
+    #[test]
+    #[should_panic(expected = "must be the last segment")]
+    fn rest_must_be_last() {
+        let _ = Router::new().get("/*path/more", echo("bad"));
+    }
+}
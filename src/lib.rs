@@ -1,43 +1,671 @@
-use crossbeam::channel;
+use crossbeam::{channel, sync::ShardedLock};
 use log::{error, info, warn};
 use std::{
-    sync::{Arc, Mutex},
+    any::Any,
+    io, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use thiserror::Error;
 
+mod builder;
+mod cancel;
+#[cfg(feature = "deterministic")]
+mod deterministic;
+#[cfg(feature = "async")]
+mod future;
+mod handle;
+pub mod http;
+mod par;
+mod queue;
+pub mod registry;
+mod scope;
+mod state;
+mod stats;
+mod timer;
+mod watchdog;
+
+pub use builder::ThreadPoolBuilder;
+use builder::WorkerConfig;
+pub use cancel::CancellationToken;
+#[cfg(feature = "async")]
+pub use future::block_on;
+pub use handle::{JobHandle, JoinError};
+use queue::{Lanes, Queue};
+pub use scope::Scope;
+pub use state::StatefulPool;
+use stats::{Histogram, WorkerCounters};
+pub use stats::{HistogramSnapshot, PoolStats, WorkerStats};
+pub use timer::ScheduleHandle;
+use timer::Timer;
+use watchdog::{Deadline, Running, Watchdog};
+
 type Job = Box<dyn FnOnce() + Send + 'static>;
+
+struct Task {
+    job: Job,
+    // Only stamped while something needs queue latency, reading the clock on
+    // every submit is measurable with tiny jobs.
+    enqueued: Option<Instant>,
+    // Boxed to keep plain tasks small.
+    control: Option<Box<Control>>,
+}
+
+struct Control {
+    token: CancellationToken,
+    timeout: Option<Duration>,
+    label: Option<Arc<str>>,
+}
+
+impl Task {
+    fn new(job: Job, timed: bool) -> Task {
+        Task {
+            job,
+            enqueued: timed.then(Instant::now),
+            control: None,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.control
+            .as_ref()
+            .is_some_and(|control| control.token.is_cancelled())
+    }
+}
+
+// How often idle workers wake up to check whether the pool has shrunk.
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Autoscale {
+    pub min: usize,
+    pub max: usize,
+    // Idle workers above `min` retire after this long without a job.
+    pub keep_alive: Duration,
+    // A worker is added when a job waited in the queue longer than this.
+    pub scale_up_wait: Duration,
+}
+
+impl Autoscale {
+    pub fn new(min: usize, max: usize) -> Autoscale {
+        Autoscale {
+            min,
+            max,
+            keep_alive: Duration::from_secs(30),
+            scale_up_wait: Duration::from_millis(20),
+        }
+    }
+
+    fn validate(&self) -> Result<(), PoolCreationError> {
+        if self.min == 0 {
+            Err(PoolCreationError::SizeZero)
+        } else if self.min > self.max {
+            Err(PoolCreationError::MinAboveMax {
+                min: self.min,
+                max: self.max,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+struct Scaling {
+    min: usize,
+    max: usize,
+    keep_alive: Duration,
+    scale_up_wait: Option<Duration>,
+}
+
+impl Scaling {
+    fn fixed(size: usize) -> Scaling {
+        Scaling {
+            min: size,
+            max: size,
+            keep_alive: Duration::ZERO,
+            scale_up_wait: None,
+        }
+    }
+}
+
+impl From<Autoscale> for Scaling {
+    fn from(autoscale: Autoscale) -> Scaling {
+        Scaling {
+            min: autoscale.min,
+            max: autoscale.max,
+            keep_alive: autoscale.keep_alive,
+            scale_up_wait: Some(autoscale.scale_up_wait),
+        }
+    }
+}
+
+struct Shared {
+    queue: Queue,
+    workers: Mutex<Vec<Worker>>,
+    size: AtomicUsize,
+    scaling: ShardedLock<Scaling>,
+    // Mirrors of `scaling` read on every job.
+    max: AtomicUsize,
+    timed: AtomicBool,
+    config: WorkerConfig,
+    shutting_down: AtomicBool,
+    alive: Mutex<usize>,
+    exited: Condvar,
+    completed: AtomicUsize,
+    panicked: AtomicUsize,
+    cancelled: AtomicUsize,
+    timed_out: AtomicUsize,
+    starved: AtomicUsize,
+    // Workers running a job past its deadline. Up to `max` of them get a
+    // replacement and stop counting against the pool's size limits, see `stand_ins`.
+    stuck: AtomicUsize,
+    queue_wait: Histogram,
+    execution: Histogram,
+    #[cfg(feature = "deterministic")]
+    scheduler: Option<deterministic::Scheduler>,
+}
+
+impl Shared {
+    fn set_scaling(&self, scaling: Scaling) {
+        let mut current = self.scaling.write().unwrap();
+        self.max.store(scaling.max, Ordering::SeqCst);
+        self.timed.store(
+            self.config.timings || scaling.scale_up_wait.is_some(),
+            Ordering::SeqCst,
+        );
+        *current = scaling;
+    }
+
+    fn stats(&self) -> PoolStats {
+        let workers: Vec<WorkerStats> = {
+            let workers = self.workers.lock().unwrap();
+            workers.iter().map(|w| w.counters.snapshot(w.id)).collect()
+        };
+        let active = workers.iter().filter(|w| w.active).count();
+        PoolStats {
+            queued: self.queue.len(),
+            active,
+            idle: workers.len() - active,
+            completed: self.completed.load(Ordering::Relaxed),
+            panicked: self.panicked.load(Ordering::Relaxed),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+            timed_out: self.timed_out.load(Ordering::Relaxed),
+            starved: self.starved.load(Ordering::Relaxed),
+            workers,
+            queue_wait: self.queue_wait.snapshot(),
+            execution: self.execution.snapshot(),
+        }
+    }
+
+    fn run(&self, id: usize, counters: &WorkerCounters, task: Task) {
+        if task.is_cancelled() {
+            info!("Worker {} skipping cancelled job", id);
+            self.cancelled.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        info!("Worker {} got a job: executing", id);
+        let started = self.config.timings.then(Instant::now);
+        if let (Some(enqueued), Some(started)) = (task.enqueued, started) {
+            self.queue_wait.record(started - enqueued);
+        }
+        let control = task.control.as_deref();
+        let watched = self.config.slow_threshold.is_some()
+            || control.is_some_and(|control| control.timeout.is_some());
+        if watched {
+            let now = started.unwrap_or_else(Instant::now);
+            *counters.running.lock().unwrap() = Some(Running {
+                started: now,
+                label: control.and_then(|control| control.label.clone()),
+                deadline: control.and_then(|control| {
+                    Some(Deadline {
+                        at: now + control.timeout?,
+                        token: control.token.clone(),
+                        overdue: false,
+                    })
+                }),
+                slow: false,
+            });
+        }
+        counters.active.store(true, Ordering::Relaxed);
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(task.job)) {
+            self.panicked.fetch_add(1, Ordering::Relaxed);
+            counters.panicked.fetch_add(1, Ordering::Relaxed);
+            error!(
+                "Worker {} job panicked: {}",
+                id,
+                panic_message(payload.as_ref())
+            );
+        }
+        counters.active.store(false, Ordering::Relaxed);
+        if watched {
+            let running = counters.running.lock().unwrap().take();
+            if running
+                .and_then(|running| running.deadline)
+                .is_some_and(|deadline| deadline.overdue)
+            {
+                self.stuck.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+        if let Some(started) = started {
+            let elapsed = started.elapsed();
+            self.execution.record(elapsed);
+            counters
+                .busy_nanos
+                .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        }
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        counters.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Returns false if the deadline passed before every worker exited. A worker
+    // shutting down its own pool doesn't wait for itself.
+    fn wait_for_workers(&self, deadline: Instant) -> bool {
+        // There are no workers to drain the queue, so do it here like they would.
+        #[cfg(feature = "deterministic")]
+        if self.scheduler.is_some() {
+            while Instant::now() < deadline && self.run_next() {}
+            return self.queue.len() == 0 && self.scheduler.as_ref().is_some_and(|s| s.len() == 0);
+        }
+        let own = usize::from(self.queue.worker_id().is_some());
+        let mut alive = self.alive.lock().unwrap();
+        while *alive > own {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            alive = self.exited.wait_timeout(alive, deadline - now).unwrap().0;
+        }
+        true
+    }
+
+    // Removes every queued task without running it and returns how many there were.
+    fn drain(&self) -> usize {
+        #[cfg(feature = "deterministic")]
+        if let Some(scheduler) = &self.scheduler {
+            return scheduler.drain() + self.queue.drain();
+        }
+        self.queue.drain()
+    }
+
+    fn take_workers(&self) -> Vec<Worker> {
+        let mut workers = self.workers.lock().unwrap();
+        self.shutting_down.store(true, Ordering::SeqCst);
+        self.size.store(0, Ordering::SeqCst);
+        mem::take(&mut *workers)
+    }
+
+    fn spawn_worker(shared: &Arc<Shared>, workers: &mut Vec<Worker>) -> io::Result<()> {
+        let id = (0..)
+            .find(|id| workers.iter().all(|w| w.id != *id))
+            .unwrap();
+        info!("Spawning worker {}", id);
+        workers.push(Worker::new(id, Arc::clone(shared))?);
+        shared.size.store(workers.len(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn grow(shared: &Arc<Shared>, size: usize) -> io::Result<()> {
+        let mut workers = shared.workers.lock().unwrap();
+        if shared.shutting_down.load(Ordering::SeqCst) || shared.is_deterministic() {
+            return Ok(());
+        }
+        while workers.len() < size {
+            Shared::spawn_worker(shared, &mut workers)?;
+        }
+        Ok(())
+    }
+
+    fn scale_up(shared: &Arc<Shared>, waited: Duration) {
+        let max = {
+            let scaling = shared.scaling.read().unwrap();
+            match scaling.scale_up_wait {
+                Some(threshold) if waited > threshold => scaling.max,
+                _ => return,
+            }
+        };
+        let max = max + shared.stand_ins();
+        if shared.size.load(Ordering::SeqCst) >= max {
+            return;
+        }
+        let mut workers = shared.workers.lock().unwrap();
+        if shared.shutting_down.load(Ordering::SeqCst) || workers.len() >= max {
+            return;
+        }
+        info!("Job waited {:?} in the queue, adding a worker", waited);
+        if let Err(e) = Shared::spawn_worker(shared, &mut workers) {
+            error!("Failed to add a worker: {e}");
+        }
+    }
+
+    // Removes the worker from the pool if it is above the allowed size. An idle
+    // worker counts against `min`, a busy one only against `max`.
+    fn try_retire(&self, id: usize, idle: Option<Duration>) -> bool {
+        let stuck = self.stand_ins();
+        if idle.is_none()
+            && self.size.load(Ordering::SeqCst) <= self.max.load(Ordering::SeqCst) + stuck
+        {
+            return false;
+        }
+        let limit = stuck + {
+            let scaling = self.scaling.read().unwrap();
+            match idle {
+                Some(idle) if idle >= scaling.keep_alive => scaling.min,
+                _ => scaling.max,
+            }
+        };
+        if self.size.load(Ordering::SeqCst) <= limit {
+            return false;
+        }
+        let mut workers = self.workers.lock().unwrap();
+        if self.shutting_down.load(Ordering::SeqCst) || workers.len() <= limit {
+            return false;
+        }
+        let Some(index) = workers.iter().position(|w| w.id == id) else {
+            return false;
+        };
+        workers.swap_remove(index);
+        self.size.store(workers.len(), Ordering::SeqCst);
+        info!("Worker {} retiring", id);
+        true
+    }
+
+    // Stuck workers the pool has replaced. Capped at `max` so jobs that ignore
+    // their token can't grow the pool without bound.
+    fn stand_ins(&self) -> usize {
+        let max = self.max.load(Ordering::SeqCst);
+        self.stuck.load(Ordering::SeqCst).min(max)
+    }
+
+    fn is_deterministic(&self) -> bool {
+        #[cfg(feature = "deterministic")]
+        return self.scheduler.is_some();
+        #[cfg(not(feature = "deterministic"))]
+        false
+    }
+
+    // Whether `help` may find something to run on the calling thread.
+    fn can_help(&self) -> bool {
+        self.is_deterministic() || self.queue.worker_id().is_some()
+    }
+
+    // Runs the next job picked by the deterministic scheduler, if any.
+    #[cfg(feature = "deterministic")]
+    fn run_next(&self) -> bool {
+        let Some(scheduler) = &self.scheduler else {
+            return false;
+        };
+        match scheduler.next(&self.queue) {
+            Some(task) => {
+                self.run(0, &scheduler.counters, task);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Runs one queued job on the calling worker while it waits on something else,
+    // returns false if there was nothing to run or the caller isn't a worker.
+    fn help(&self) -> bool {
+        #[cfg(feature = "deterministic")]
+        if self.scheduler.is_some() {
+            return self.run_next();
+        }
+        let Some(id) = self.queue.worker_id() else {
+            return false;
+        };
+        let Some(task) = self.queue.find(id) else {
+            return false;
+        };
+        let counters = {
+            let workers = self.workers.lock().unwrap();
+            let worker = workers.iter().find(|w| w.id == id);
+            worker.map_or_else(
+                || Arc::new(WorkerCounters::new()),
+                |w| Arc::clone(&w.counters),
+            )
+        };
+        // The outer job is still running, keep its deadline out of the nested run.
+        let outer = counters.running.lock().unwrap().take();
+        self.run(id, &counters, task);
+        *counters.running.lock().unwrap() = outer;
+        counters.active.store(true, Ordering::Relaxed);
+        true
+    }
+
+    fn respawn(shared: &Arc<Shared>, id: usize) {
+        let mut workers = shared.workers.lock().unwrap();
+        if shared.shutting_down.load(Ordering::SeqCst) {
+            return;
+        }
+        warn!("Worker {} died, spawning a replacement", id);
+        let replacement = match Worker::new(id, Arc::clone(shared)) {
+            Ok(replacement) => replacement,
+            Err(e) => {
+                error!("Failed to respawn worker {}: {e}", id);
+                workers.retain(|w| w.id != id);
+                shared.size.store(workers.len(), Ordering::SeqCst);
+                return;
+            }
+        };
+        match workers.iter_mut().find(|w| w.id == id) {
+            Some(slot) => *slot = replacement,
+            None => workers.push(replacement),
+        }
+        shared.size.store(workers.len(), Ordering::SeqCst);
+    }
+}
+
 struct Worker {
     id: usize,
     thread: Option<JoinHandle<()>>,
+    counters: Arc<WorkerCounters>,
+}
+
+// Respawns the worker if its thread unwinds outside of a caught job panic.
+struct Sentinel {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Drop for Sentinel {
+    fn drop(&mut self) {
+        self.shared.queue.unregister(self.id);
+        if let Some(on_stop) = &self.shared.config.on_stop {
+            run_hook("on_thread_stop", self.id, on_stop.as_ref());
+        }
+        if thread::panicking() {
+            Shared::respawn(&self.shared, self.id);
+        }
+        *self.shared.alive.lock().unwrap() -= 1;
+        self.shared.exited.notify_all();
+    }
 }
 
 impl Worker {
-    fn new(id: usize, rx: Arc<Mutex<channel::Receiver<Job>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            let message = rx.lock().unwrap().recv();
-            match message {
-                Ok(job) => {
-                    info!("Worker {} got a job: executing", id);
-                    job();
+    fn new(id: usize, shared: Arc<Shared>) -> io::Result<Worker> {
+        *shared.alive.lock().unwrap() += 1;
+        let builder = shared.config.thread_builder(id);
+        let owner = Arc::clone(&shared);
+        let counters = Arc::new(WorkerCounters::new());
+        let thread_counters = Arc::clone(&counters);
+        let thread = builder.spawn(move || {
+            let counters = thread_counters;
+            let sentinel = Sentinel { id, shared };
+            let shared = &sentinel.shared;
+            #[cfg(target_os = "linux")]
+            if let Some(cpu) = shared.config.cpu_for(id) {
+                if let Err(e) = pin_to_cpu(cpu) {
+                    warn!("Failed to pin worker {} to CPU {}: {e}", id, cpu);
                 }
-                Err(_) => {
-                    error!("Working {} disconnected, shutting down...", id);
+            }
+            shared.queue.register(id);
+            if let Some(on_start) = &shared.config.on_start {
+                run_hook("on_thread_start", id, on_start.as_ref());
+            }
+            let mut idle_polls = 0;
+            loop {
+                let task = match shared.queue.find(id) {
+                    Some(task) => task,
+                    None => match shared.queue.wait(IDLE_POLL) {
+                        Ok(task) => task,
+                        Err(channel::RecvTimeoutError::Timeout) => {
+                            idle_polls += 1;
+                            if shared.try_retire(id, Some(IDLE_POLL * idle_polls)) {
+                                break;
+                            }
+                            continue;
+                        }
+                        Err(channel::RecvTimeoutError::Disconnected) => {
+                            error!("Working {} disconnected, shutting down...", id);
+                            break;
+                        }
+                    },
+                };
+                if let Some(enqueued) = task.enqueued {
+                    Shared::scale_up(shared, enqueued.elapsed());
+                }
+                shared.run(id, &counters, task);
+                if shared.queue.local_is_empty() && shared.try_retire(id, None) {
                     break;
                 }
+                idle_polls = 0;
             }
         });
-        Worker {
+        match thread {
+            Ok(thread) => Ok(Worker {
+                id,
+                thread: Some(thread),
+                counters,
+            }),
+            Err(e) => {
+                *owner.alive.lock().unwrap() -= 1;
+                Err(e)
+            }
+        }
+    }
+}
+
+// A panicking hook would otherwise kill the worker before it takes a job, and its
+// replacement would run the same hook again.
+fn run_hook(name: &str, id: usize, hook: &(dyn Fn(usize) + Send + Sync)) {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| hook(id))) {
+        error!(
+            "Worker {} {name} hook panicked: {}",
             id,
-            thread: Some(thread),
+            panic_message(payload.as_ref())
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pin_to_cpu(cpu: usize) -> io::Result<()> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+    // SAFETY: `set` is a plain bitmask owned by this frame, `cpu` is within its
+    // bounds, and pid 0 targets the calling thread.
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
         }
     }
+    Ok(())
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    const COUNT: usize = 3;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JobOptions {
+    pub priority: Priority,
+    // The job's token is cancelled once it has run this long, and a replacement
+    // worker is spawned so the pool doesn't lose capacity to it.
+    pub timeout: Option<Duration>,
+    // Names the job in watchdog warnings.
+    pub label: Option<Arc<str>>,
+}
+
+impl JobOptions {
+    pub fn new() -> JobOptions {
+        JobOptions::default()
+    }
+
+    pub fn priority(mut self, priority: Priority) -> JobOptions {
+        self.priority = priority;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> JobOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<Arc<str>>) -> JobOptions {
+        self.label = Some(label.into());
+        self
+    }
+}
+
+enum Submit {
+    Block,
+    Try,
+    Timeout(Duration),
 }
 
 pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: Option<channel::Sender<Job>>,
+    shared: Arc<Shared>,
+    // Shared so wakers can submit without keeping the lanes open after shutdown.
+    sender: Arc<ShardedLock<Option<Lanes>>>,
+    // Started on the first delayed or periodic job.
+    timer: Mutex<Option<Timer>>,
+    // Started on the first job with a timeout.
+    watchdog: Mutex<Option<Watchdog>>,
+}
+
+#[derive(Clone)]
+pub struct PoolMonitor {
+    shared: Arc<Shared>,
+}
+
+impl PoolMonitor {
+    pub fn stats(&self) -> PoolStats {
+        self.shared.stats()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    // Jobs that finished after shutdown began, including ones already running.
+    pub completed: usize,
+    // Queued jobs that were dropped without running.
+    pub abandoned: usize,
 }
 
 #[derive(Debug, Error)]
@@ -45,54 +673,477 @@ pub struct ThreadPool {
 pub enum PoolCreationError {
     #[error("Size of thread pool cannot be zero")]
     SizeZero,
+    #[error("Minimum pool size {min} exceeds maximum {max}")]
+    MinAboveMax { min: usize, max: usize },
+    #[error("Pool size {size} is outside the autoscaling range {min}..={max}")]
+    SizeOutOfRange { size: usize, min: usize, max: usize },
+    #[error("CPU {0} is out of range for affinity")]
+    InvalidCpu(usize),
+    #[error("Failed to spawn worker thread: {0}")]
+    Spawn(#[from] io::Error),
+}
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ExecuteError {
+    #[error("Thread pool has been shut down")]
+    ShutDown,
+    #[error("Job queue is full")]
+    QueueFull,
+    #[error("Timed out waiting for space in the job queue")]
+    Timeout,
+    #[error("All workers in the thread pool have died")]
+    WorkersDead,
+    #[error("Delay or interval is zero or out of range")]
+    InvalidDelay,
+    #[error("Failed to start timer thread: {0}")]
+    Timer(io::Error),
+    #[error("Failed to start watchdog thread: {0}")]
+    Watchdog(io::Error),
 }
+
 impl ThreadPool {
     pub fn new(size: usize) -> ThreadPool {
         assert!(size > 0);
-        let mut workers: Vec<Worker> = Vec::new();
-        let (tx, rx): (channel::Sender<Job>, channel::Receiver<Job>) = channel::unbounded();
-        let rx = Arc::new(Mutex::new(rx));
-        for i in 0..size {
-            workers.push(Worker::new(i, Arc::clone(&rx)));
+        ThreadPool::builder().size(size).build().unwrap()
+    }
+    pub fn build(size: usize) -> Result<ThreadPool, PoolCreationError> {
+        ThreadPool::builder().size(size).build()
+    }
+    pub fn build_bounded(size: usize, capacity: usize) -> Result<ThreadPool, PoolCreationError> {
+        ThreadPool::builder()
+            .size(size)
+            .queue_capacity(capacity)
+            .build()
+    }
+    pub fn builder() -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new()
+    }
+
+    fn start(
+        size: usize,
+        capacity: Option<usize>,
+        scaling: Scaling,
+        config: WorkerConfig,
+    ) -> Result<ThreadPool, PoolCreationError> {
+        let (lanes, queue) = queue::lanes(capacity);
+        #[cfg(feature = "deterministic")]
+        let seed = config.seed;
+        let shared = Arc::new(Shared {
+            queue,
+            workers: Mutex::new(Vec::with_capacity(size)),
+            size: AtomicUsize::new(0),
+            max: AtomicUsize::new(scaling.max),
+            timed: AtomicBool::new(config.timings || scaling.scale_up_wait.is_some()),
+            scaling: ShardedLock::new(scaling),
+            config,
+            shutting_down: AtomicBool::new(false),
+            alive: Mutex::new(0),
+            exited: Condvar::new(),
+            completed: AtomicUsize::new(0),
+            panicked: AtomicUsize::new(0),
+            cancelled: AtomicUsize::new(0),
+            timed_out: AtomicUsize::new(0),
+            starved: AtomicUsize::new(0),
+            stuck: AtomicUsize::new(0),
+            queue_wait: Histogram::new(),
+            execution: Histogram::new(),
+            #[cfg(feature = "deterministic")]
+            scheduler: seed.map(deterministic::Scheduler::new),
+        });
+        let pool = ThreadPool {
+            shared,
+            sender: Arc::new(ShardedLock::new(Some(lanes))),
+            timer: Mutex::new(None),
+            watchdog: Mutex::new(None),
+        };
+        Shared::grow(&pool.shared, size)?;
+        if pool.shared.config.slow_threshold.is_some() {
+            *pool.watchdog.lock().unwrap() = Some(Watchdog::start(Arc::clone(&pool.shared))?);
         }
-        ThreadPool {
-            workers,
-            sender: Some(tx),
+        Ok(pool)
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.shared.stats()
+    }
+
+    // A cheap handle for reading stats from code that doesn't own the pool.
+    pub fn monitor(&self) -> PoolMonitor {
+        PoolMonitor {
+            shared: Arc::clone(&self.shared),
         }
     }
-    pub fn build(size: usize) -> Result<ThreadPool, PoolCreationError> {
-        match size {
-            0 => Err(PoolCreationError::SizeZero),
-            _ => {
-                let mut workers: Vec<Worker> = Vec::new();
-                let (tx, rx): (channel::Sender<Job>, channel::Receiver<Job>) = channel::unbounded();
-                let rx = Arc::new(Mutex::new(rx));
-                for i in 0..size {
-                    workers.push(Worker::new(i, Arc::clone(&rx)));
+
+    pub fn panic_count(&self) -> usize {
+        self.shared.panicked.load(Ordering::Relaxed)
+    }
+
+    pub fn size(&self) -> usize {
+        self.shared.size.load(Ordering::SeqCst)
+    }
+
+    // Fixes the pool at `size` workers. Extra workers retire once they finish
+    // their current job.
+    pub fn resize(&self, size: usize) -> Result<(), PoolCreationError> {
+        if size == 0 {
+            return Err(PoolCreationError::SizeZero);
+        }
+        self.shared.set_scaling(Scaling::fixed(size));
+        Shared::grow(&self.shared, size)?;
+        Ok(())
+    }
+
+    pub fn autoscale(&self, autoscale: Autoscale) -> Result<(), PoolCreationError> {
+        autoscale.validate()?;
+        self.shared.set_scaling(Scaling::from(autoscale));
+        Shared::grow(&self.shared, autoscale.min)?;
+        Ok(())
+    }
+
+    fn task<F>(&self, f: F) -> Task
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        Task::new(Box::new(f), self.shared.timed.load(Ordering::Relaxed))
+    }
+
+    fn lanes(&self) -> Result<Lanes, ExecuteError> {
+        let sender = self.sender.read().unwrap();
+        sender.as_ref().cloned().ok_or(ExecuteError::ShutDown)
+    }
+
+    fn submit(&self, task: Task, priority: Priority, submit: Submit) -> Result<(), ExecuteError> {
+        let lanes = self.lanes()?;
+        // The pool holds its own receivers, so the lanes never disconnect when the
+        // last worker is gone and the send would block or queue forever.
+        if self.shared.size.load(Ordering::SeqCst) == 0 && !self.shared.is_deterministic() {
+            return Err(ExecuteError::WorkersDead);
+        }
+        let task = match priority {
+            Priority::Normal => match self.shared.queue.push_local(task) {
+                Ok(()) => return Ok(()),
+                Err(task) => task,
+            },
+            _ => task,
+        };
+        // Nothing drains a deterministic pool's queue until `run_until_idle` runs
+        // on some thread, likely this one, so waiting for space would never end.
+        let submit = if self.shared.is_deterministic() {
+            Submit::Try
+        } else {
+            submit
+        };
+        match submit {
+            Submit::Block => lanes
+                .send(priority, task)
+                .map_err(|_| ExecuteError::WorkersDead),
+            Submit::Try => lanes.try_send(priority, task).map_err(|e| match e {
+                channel::TrySendError::Full(_) => ExecuteError::QueueFull,
+                channel::TrySendError::Disconnected(_) => ExecuteError::WorkersDead,
+            }),
+            Submit::Timeout(timeout) => {
+                lanes
+                    .send_timeout(priority, task, timeout)
+                    .map_err(|e| match e {
+                        channel::SendTimeoutError::Timeout(_) => ExecuteError::Timeout,
+                        channel::SendTimeoutError::Disconnected(_) => ExecuteError::WorkersDead,
+                    })
+            }
+        }
+    }
+
+    // How many jobs may wait in the queue across all priorities. Normal priority
+    // jobs a worker submits to its own pool don't count.
+    pub fn capacity(&self) -> Option<usize> {
+        let sender = self.sender.read().unwrap();
+        sender.as_ref().and_then(Lanes::capacity)
+    }
+
+    pub fn queued(&self) -> usize {
+        #[cfg(feature = "deterministic")]
+        if let Some(scheduler) = &self.shared.scheduler {
+            return self.shared.queue.len() + scheduler.len();
+        }
+        self.shared.queue.len()
+    }
+
+    // Runs queued jobs, including any they submit, on the calling thread until
+    // none are left, and returns how many ran. Only for pools built with
+    // `ThreadPoolBuilder::deterministic`.
+    #[cfg(feature = "deterministic")]
+    pub fn run_until_idle(&self) -> usize {
+        assert!(
+            self.shared.is_deterministic(),
+            "run_until_idle needs a deterministic pool"
+        );
+        let mut ran = 0;
+        while self.shared.run_next() {
+            ran += 1;
+        }
+        ran
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.sender.read().unwrap().is_none()
+    }
+
+    // Stops accepting jobs, lets workers drain the queue until the deadline, then
+    // abandons whatever is still queued. Workers stuck past the deadline are detached.
+    pub fn shutdown(&self, deadline: Instant) -> ShutdownReport {
+        let completed_before = self.shared.completed.load(Ordering::SeqCst);
+        self.stop_timer();
+        self.stop_watchdog();
+        if self.sender.write().unwrap().take().is_none() {
+            return ShutdownReport::default();
+        }
+        let drained = self.shared.wait_for_workers(deadline);
+        let abandoned = if drained { 0 } else { self.shared.drain() };
+        let current = self.shared.queue.worker_id();
+        for mut worker in self.shared.take_workers() {
+            let Some(thread) = worker.thread.take() else {
+                continue;
+            };
+            if current == Some(worker.id) {
+                warn!("Worker {} shut down its own pool, detaching", worker.id);
+            } else if drained || thread.is_finished() {
+                warn!("Shutting down worker {}", worker.id);
+                if thread.join().is_err() {
+                    error!("Worker {} panicked while shutting down", worker.id);
                 }
-                Ok(ThreadPool {
-                    workers,
-                    sender: Some(tx),
-                })
+            } else {
+                warn!(
+                    "Worker {} still busy past shutdown deadline, detaching",
+                    worker.id
+                );
             }
         }
+        let report = ShutdownReport {
+            completed: self.shared.completed.load(Ordering::SeqCst) - completed_before,
+            abandoned,
+        };
+        info!(
+            "Thread pool shut down: {} jobs completed, {} abandoned",
+            report.completed, report.abandoned
+        );
+        report
+    }
+
+    pub fn shutdown_now(&self) -> ShutdownReport {
+        self.shutdown(Instant::now())
     }
 
-    pub fn execute<F>(&self, f: F)
+    // Blocks while the queue is full.
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
     where
         F: FnOnce() + Send + 'static,
     {
-        let job = Box::new(f);
-        self.sender.as_ref().unwrap().send(job).unwrap();
+        self.submit(self.task(f), Priority::Normal, Submit::Block)
+    }
+
+    pub fn execute_with_priority<F>(&self, f: F, priority: Priority) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.submit(self.task(f), priority, Submit::Block)
+    }
+
+    // Runs `f` with a scope whose jobs may borrow from the caller, and waits for all
+    // of them before returning. If any job panicked, so does `scope`.
+    pub fn scope<'env, F, T>(&self, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        scope::run(self, f)
+    }
+
+    pub fn spawn<F, T>(&self, f: F) -> Result<JobHandle<T>, ExecuteError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_with_priority(f, Priority::Normal)
+    }
+
+    pub fn spawn_with_priority<F, T>(
+        &self,
+        f: F,
+        priority: Priority,
+    ) -> Result<JobHandle<T>, ExecuteError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_with(JobOptions::new().priority(priority), |_| f())
+    }
+
+    // Like `execute`, but the job gets a token it can poll to stop early. Returns a
+    // clone of that token, cancelling it before the job starts keeps it from running.
+    pub fn execute_with<F>(
+        &self,
+        options: JobOptions,
+        f: F,
+    ) -> Result<CancellationToken, ExecuteError>
+    where
+        F: FnOnce(&CancellationToken) + Send + 'static,
+    {
+        let token = CancellationToken::new();
+        let job_token = token.clone();
+        self.submit_with(options, token.clone(), Submit::Block, move || f(&job_token))?;
+        Ok(token)
+    }
+
+    pub fn try_execute_with<F>(
+        &self,
+        options: JobOptions,
+        f: F,
+    ) -> Result<CancellationToken, ExecuteError>
+    where
+        F: FnOnce(&CancellationToken) + Send + 'static,
+    {
+        let token = CancellationToken::new();
+        let job_token = token.clone();
+        self.submit_with(options, token.clone(), Submit::Try, move || f(&job_token))?;
+        Ok(token)
+    }
+
+    pub fn spawn_with<F, T>(&self, options: JobOptions, f: F) -> Result<JobHandle<T>, ExecuteError>
+    where
+        F: FnOnce(&CancellationToken) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = channel::bounded(1);
+        let token = CancellationToken::new();
+        let job_token = token.clone();
+        let job = move || match panic::catch_unwind(AssertUnwindSafe(|| f(&job_token))) {
+            Ok(value) => {
+                let _ = tx.send(Ok(value));
+            }
+            Err(payload) => {
+                let message = panic_message(payload.as_ref()).to_string();
+                let _ = tx.send(Err(JoinError::Panicked(message)));
+                // Let the worker see the panic so it is logged and counted.
+                panic::resume_unwind(payload);
+            }
+        };
+        self.submit_with(options, token.clone(), Submit::Block, job)?;
+        Ok(JobHandle::new(rx, token))
+    }
+
+    fn submit_with<F>(
+        &self,
+        options: JobOptions,
+        token: CancellationToken,
+        mode: Submit,
+        f: F,
+    ) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if options.timeout.is_some() {
+            self.start_watchdog()?;
+        }
+        let mut task = self.task(f);
+        task.control = Some(Box::new(Control {
+            token,
+            timeout: options.timeout,
+            label: options.label,
+        }));
+        self.submit(task, options.priority, mode)
+    }
+
+    fn start_watchdog(&self) -> Result<(), ExecuteError> {
+        let mut watchdog = self.watchdog.lock().unwrap();
+        if watchdog.is_none() {
+            *watchdog =
+                Some(Watchdog::start(Arc::clone(&self.shared)).map_err(ExecuteError::Watchdog)?);
+        }
+        Ok(())
+    }
+
+    fn stop_watchdog(&self) {
+        if let Some(mut watchdog) = self.watchdog.lock().unwrap().take() {
+            watchdog.stop();
+        }
+    }
+
+    pub fn try_execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.submit(self.task(f), Priority::Normal, Submit::Try)
+    }
+
+    pub fn try_execute_with_priority<F>(&self, f: F, priority: Priority) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.submit(self.task(f), priority, Submit::Try)
+    }
+
+    pub fn execute_timeout<F>(&self, f: F, timeout: Duration) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.submit(self.task(f), Priority::Normal, Submit::Timeout(timeout))
+    }
+
+    fn with_timer<R>(&self, f: impl FnOnce(&mut Timer) -> R) -> Result<R, ExecuteError> {
+        let mut timer = self.timer.lock().unwrap();
+        if timer.is_none() {
+            let lanes = self.lanes()?;
+            *timer =
+                Some(Timer::start(Arc::clone(&self.shared), lanes).map_err(ExecuteError::Timer)?);
+        }
+        Ok(f(timer.as_mut().unwrap()))
+    }
+
+    fn stop_timer(&self) {
+        if let Some(mut timer) = self.timer.lock().unwrap().take() {
+            timer.stop();
+        }
+    }
+
+    // Runs `f` on a worker once `delay` has passed.
+    pub fn execute_after<F>(&self, delay: Duration, f: F) -> Result<ScheduleHandle, ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if Instant::now().checked_add(delay).is_none() {
+            return Err(ExecuteError::InvalidDelay);
+        }
+        self.with_timer(|timer| timer.schedule_once(delay, Box::new(f)))
+    }
+
+    // Runs `f` on a worker every `interval`, starting one interval from now. A tick
+    // is skipped if the previous run hasn't finished.
+    pub fn execute_every<F>(&self, interval: Duration, f: F) -> Result<ScheduleHandle, ExecuteError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        if interval.is_zero() || Instant::now().checked_add(interval).is_none() {
+            return Err(ExecuteError::InvalidDelay);
+        }
+        self.with_timer(|timer| timer.schedule_every(interval, Arc::new(f)))
     }
 }
 impl Drop for ThreadPool {
     fn drop(&mut self) {
-        drop(self.sender.take());
-        for worker in &mut self.workers {
+        self.stop_timer();
+        self.stop_watchdog();
+        drop(self.sender.write().unwrap().take());
+        // The last handle may be dropped by one of the pool's own jobs, and a
+        // thread can't join itself.
+        let current = self.shared.queue.worker_id();
+        for mut worker in self.shared.take_workers() {
+            if current == Some(worker.id) {
+                warn!("Worker {} dropped its own pool, detaching", worker.id);
+                continue;
+            }
             warn!("Shutting down worker {}", worker.id);
             if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+                if thread.join().is_err() {
+                    error!("Worker {} panicked while shutting down", worker.id);
+                }
             }
         }
     }
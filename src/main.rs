@@ -1,35 +1,253 @@
 use std::{
-    fs,
-    io::{BufRead, BufReader, Write},
+    env, fs,
+    io::{self, BufReader, Write},
     net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
-use log::{info, warn};
-use simple_http_server::ThreadPool;
+use log::{error, info, warn};
+use simple_http_server::{
+    http::{Body, Limits, Request, Response, Router, StaticFiles, Status},
+    registry, Autoscale, ExecuteError, JobOptions, PoolMonitor, Priority, ThreadPool,
+};
+
+const MIN_WORKERS: usize = 2;
+const MAX_WORKERS: usize = 16;
+const BACKGROUND_WORKERS: usize = 2;
+const QUEUE_CAPACITY: usize = 64;
+const ADDRESS: &str = "127.0.0.1:7878";
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const SLOW_REQUEST: Duration = Duration::from_secs(5);
+// Overridden by the DOCUMENT_ROOT environment variable.
+const DEFAULT_DOCUMENT_ROOT: &str = "./html";
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
 fn main() {
     env_logger::init();
-    let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
-    let pool = ThreadPool::new(4);
+    let listener = TcpListener::bind(ADDRESS).unwrap();
+    let background = ThreadPool::builder()
+        .size(BACKGROUND_WORKERS)
+        .thread_name("background");
+    let background = registry::register("background", background).unwrap();
+    let pool = ThreadPool::builder()
+        .queue_capacity(QUEUE_CAPACITY)
+        .autoscale(Autoscale::new(MIN_WORKERS, MAX_WORKERS))
+        .thread_name("http-worker")
+        .track_timings(true)
+        .slow_job_threshold(SLOW_REQUEST)
+        .build_with_state(Buffers::default)
+        .unwrap();
+    let root = env::var("DOCUMENT_ROOT").unwrap_or_else(|_| DEFAULT_DOCUMENT_ROOT.to_string());
+    let files = Arc::new(StaticFiles::new(root));
+    checksum_pages(files.root());
+    let monitor = pool.monitor();
+    background
+        .execute_every(STATS_INTERVAL, move || {
+            info!("Pool stats:\n{}", monitor.stats())
+        })
+        .unwrap();
+    let router = Arc::new(routes(pool.monitor(), files));
+    ctrlc::set_handler(|| {
+        info!("Received shutdown signal, no longer accepting connections");
+        SHUTDOWN.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it notices the flag.
+        let _ = TcpStream::connect(ADDRESS);
+    })
+    .unwrap();
     for stream in listener.incoming() {
-        let stream = stream.unwrap();
-        pool.execute(|| handle_connection(stream));
+        if SHUTDOWN.load(Ordering::SeqCst) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept connection: {e}");
+                continue;
+            }
+        };
+        let overflow = stream.try_clone();
+        let router = Arc::clone(&router);
+        let mut options = JobOptions::new()
+            .priority(classify(&stream))
+            .timeout(REQUEST_TIMEOUT);
+        if let Ok(peer) = stream.peer_addr() {
+            options = options.label(format!("connection from {peer}"));
+        }
+        let handler =
+            move |buffers: &mut Buffers, _: &_| handle_connection(stream, &router, buffers);
+        match pool.try_execute_with(options, handler) {
+            Ok(_) => {}
+            Err(ExecuteError::QueueFull) => {
+                warn!("Job queue full, shedding connection");
+                if let Ok(stream) = overflow {
+                    reject_connection(stream);
+                }
+            }
+            Err(e) => {
+                error!("Failed to dispatch connection: {e}");
+                break;
+            }
+        }
     }
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    let report = pool.shutdown(deadline);
+    info!("Final pool stats:\n{}", pool.stats());
+    info!(
+        "Server stopped: {} connections drained, {} abandoned",
+        report.completed, report.abandoned
+    );
+    registry::shutdown_all(deadline);
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let buf_reader = BufReader::new(&mut stream);
-    let request_line = buf_reader.lines().next().unwrap().unwrap();
+// Logs a checksum of every page so a bad deploy shows up in the startup log.
+fn checksum_pages(root: &Path) {
+    let Some(pool) = registry::get("background") else {
+        return;
+    };
+    let mut paths = Vec::new();
+    if let Err(e) = list_files(root, &mut paths) {
+        warn!("Failed to list pages: {e}");
+        return;
+    }
+    paths.sort();
+    let sums = pool.par_map(&paths, |path| {
+        fs::read(path).map(|contents| fnv1a(&contents))
+    });
+    for (path, sum) in paths.iter().zip(sums) {
+        match sum {
+            Ok(sum) => info!("{}: {sum:016x}", path.display()),
+            Err(e) => warn!("Failed to read {}: {e}", path.display()),
+        }
+    }
+}
 
-    let (status_line, filename) = match &request_line[..] {
-        "GET / HTTP/1.1" => ("HTTP/1.1 200 OK", "home.html"),
-        "GET /about HTTP/1.1" => ("HTTP/1.1 200 OK", "about.html"),
-        _ => ("HTTP/1.1 404 NOT FOUND", "404.html"),
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Peeks at the request line without blocking the accept loop. Health checks and
+// stats go first, then pages. Clients that haven't sent anything yet get normal
+// priority.
+fn classify(stream: &TcpStream) -> Priority {
+    let mut buf = [0; 32];
+    let peeked = stream
+        .set_nonblocking(true)
+        .and_then(|()| stream.peek(&mut buf));
+    if let Err(e) = stream.set_nonblocking(false) {
+        warn!("Failed to restore blocking mode: {e}");
+    }
+    let Ok(n) = peeked else {
+        return Priority::Normal;
     };
+    let line = &buf[..n];
+    let high: [&[u8]; 2] = [b"GET /health ", b"GET /stats "];
+    let normal: [&[u8]; 2] = [b"GET /", b"HEAD /"];
+    let complete = line.iter().filter(|&&b| b == b' ').count() >= 2;
+    if high.iter().any(|prefix| line.starts_with(prefix)) {
+        Priority::High
+    } else if normal.iter().any(|prefix| line.starts_with(prefix)) || !complete {
+        Priority::Normal
+    } else {
+        Priority::Low
+    }
+}
+
+fn reject_connection(mut stream: TcpStream) {
+    let response = Response::new(Status::ServiceUnavailable)
+        .header("Retry-After", "1")
+        .header("Connection", "close");
+    if let Err(e) = response.write_to(&mut stream) {
+        warn!("Failed to reject connection: {e}");
+    }
+}
+
+// Reused across the connections a worker handles.
+#[derive(Default)]
+struct Buffers {
+    response: Vec<u8>,
+}
+
+fn routes(monitor: PoolMonitor, files: Arc<StaticFiles>) -> Router {
+    let home = Arc::clone(&files);
+    let pages = Arc::clone(&files);
+    Router::new()
+        .get("/", move |_, _| home.file(Status::Ok, "home.html"))
+        .get("/stats", move |_, _| text(monitor.stats().to_string()))
+        .get("/health", |_, _| text("OK".to_string()))
+        .get("/*path", move |_, params| {
+            pages.serve(params.get("path").unwrap_or_default())
+        })
+        .not_found(move |_, _| files.not_found())
+}
+
+fn handle_connection(mut stream: TcpStream, router: &Router, buffers: &mut Buffers) {
+    // The handler never looks at its cancellation token, so this is what keeps a
+    // slow client from holding a worker past the request timeout.
+    let timeout = Some(REQUEST_TIMEOUT);
+    if let Err(e) = stream
+        .set_read_timeout(timeout)
+        .and_then(|()| stream.set_write_timeout(timeout))
+    {
+        warn!("Failed to set socket timeouts: {e}");
+    }
+    let Buffers { response: out } = buffers;
+    out.clear();
+    let mut buf_reader = BufReader::new(&mut stream);
+    let request = match Request::read_from(&mut buf_reader, &Limits::default()) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Bad request: {e}");
+            if let Some(status) = e.status() {
+                let response = Response::new(status).header("Connection", "close");
+                let _ = response.write_to(&mut stream);
+            }
+            return;
+        }
+    };
+
+    let response = router.handle(&request);
+    let status = response.status;
+    // This server closes every connection after one response.
+    let response = response.header("Connection", "close");
+    // Small byte bodies go out in one write from the reused buffer, files and
+    // streams are copied to the socket as they are read.
+    let written = match response.body {
+        Body::Bytes(_) => response.write_to(out).and_then(|()| stream.write_all(out)),
+        _ => response.write_to(&mut stream),
+    };
+    match written {
+        Ok(()) => info!("Response: {} {} {status}", request.method, request.target),
+        Err(e) => warn!(
+            "Failed to send {} {} {status}: {e}",
+            request.method, request.target
+        ),
+    }
+}
 
-    let contents = fs::read_to_string(format!("./html/{filename}")).unwrap();
-    let length = contents.len();
-    let response = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}");
-    stream.write_all(response.as_bytes()).unwrap();
-    info!("Response: {response}");
+fn text(body: String) -> Response {
+    Response::new(Status::Ok)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .bytes(body)
 }
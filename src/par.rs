@@ -0,0 +1,81 @@
+use std::sync::Mutex;
+
+use crate::ThreadPool;
+
+// Splits work into a few chunks per worker so a slow item doesn't leave the rest
+// of the pool idle.
+const CHUNKS_PER_WORKER: usize = 4;
+
+enum Chunk<T, R> {
+    Pending(Vec<T>),
+    Done(Vec<R>),
+}
+
+impl ThreadPool {
+    // Applies `f` to every item across the pool's workers, results come back in
+    // the original order. Chunks the pool won't take, e.g. after shutdown, run on
+    // the caller.
+    pub fn par_map<I, F, R>(&self, items: I, f: F) -> Vec<R>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> R + Sync,
+        R: Send,
+    {
+        let mut items: Vec<I::Item> = items.into_iter().collect();
+        let chunk_size = self.chunk_size(items.len());
+        let mut chunks = Vec::new();
+        while items.len() > chunk_size {
+            let rest = items.split_off(chunk_size);
+            chunks.push(Mutex::new(Chunk::Pending(items)));
+            items = rest;
+        }
+        chunks.push(Mutex::new(Chunk::Pending(items)));
+        let run = |chunk: &Mutex<Chunk<I::Item, R>>| {
+            let mut chunk = chunk.lock().unwrap();
+            if let Chunk::Pending(items) = &mut *chunk {
+                let results = items.drain(..).map(&f).collect();
+                *chunk = Chunk::Done(results);
+            }
+        };
+        self.scope(|s| {
+            for chunk in &chunks {
+                let run = &run;
+                let _ = s.spawn(move || run(chunk));
+            }
+        });
+        let mut results = Vec::new();
+        for chunk in chunks {
+            match chunk.into_inner().unwrap() {
+                Chunk::Pending(items) => results.extend(items.into_iter().map(&f)),
+                Chunk::Done(done) => results.extend(done),
+            }
+        }
+        results
+    }
+
+    pub fn par_for_each<I, F>(&self, items: I, f: F)
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) + Sync,
+    {
+        self.par_map(items, f);
+    }
+
+    // Runs `f` on each `chunk_size` piece of `items`, results come back in order.
+    pub fn par_chunks<T, F, R>(&self, items: &[T], chunk_size: usize, f: F) -> Vec<R>
+    where
+        T: Sync,
+        F: Fn(&[T]) -> R + Sync,
+        R: Send,
+    {
+        assert!(chunk_size > 0);
+        self.par_map(items.chunks(chunk_size), f)
+    }
+
+    fn chunk_size(&self, len: usize) -> usize {
+        let chunks = self.size().max(1) * CHUNKS_PER_WORKER;
+        len.div_ceil(chunks).max(1)
+    }
+}
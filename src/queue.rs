@@ -0,0 +1,390 @@
+use crossbeam::{channel, deque, sync::ShardedLock};
+use std::{
+    cell::{Cell, RefCell},
+    iter, ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{Priority, Task};
+#[cfg(feature = "ring-buffer")]
+use {crossbeam::queue::ArrayQueue, std::sync::Arc};
+
+// Every this many picks a worker checks the lanes lowest priority first, so a
+// steady stream of high priority jobs can't starve the others.
+const STARVATION_INTERVAL: usize = 8;
+
+// Jobs submitted from outside the pool go through one shared channel per priority,
+// which every worker receives from directly. Normal priority jobs a worker submits
+// to its own pool go onto that worker's local deque instead, and idle workers steal
+// from their peers' deques.
+pub(crate) struct Queue {
+    lanes: [channel::Receiver<Task>; Priority::COUNT],
+    // One message per task in the lanes of a bounded pool, freed as tasks are taken.
+    slots: Option<channel::Receiver<()>>,
+    // Tasks left on the deque of a worker that exited.
+    orphans: deque::Injector<Task>,
+    stealers: ShardedLock<Vec<(usize, Stealer)>>,
+    sleeping: AtomicUsize,
+}
+
+// A Chase-Lev deque by default. With the `ring-buffer` feature it is a fixed size
+// lock-free ring instead, and jobs that don't fit go through the shared lanes.
+#[cfg(not(feature = "ring-buffer"))]
+struct LocalQueue(deque::Worker<Task>);
+#[cfg(not(feature = "ring-buffer"))]
+type Stealer = deque::Stealer<Task>;
+
+#[cfg(not(feature = "ring-buffer"))]
+impl LocalQueue {
+    fn new() -> LocalQueue {
+        LocalQueue(deque::Worker::new_fifo())
+    }
+
+    fn stealer(&self) -> Stealer {
+        self.0.stealer()
+    }
+
+    fn push(&self, task: Task) -> Result<(), Task> {
+        self.0.push(task);
+        Ok(())
+    }
+
+    fn pop(&self) -> Option<Task> {
+        self.0.pop()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(not(feature = "ring-buffer"))]
+fn steal_one(stealer: &Stealer) -> deque::Steal<Task> {
+    stealer.steal()
+}
+
+#[cfg(feature = "ring-buffer")]
+const RING_CAPACITY: usize = 256;
+
+#[cfg(feature = "ring-buffer")]
+struct LocalQueue(Stealer);
+#[cfg(feature = "ring-buffer")]
+type Stealer = Arc<ArrayQueue<Task>>;
+
+#[cfg(feature = "ring-buffer")]
+impl LocalQueue {
+    fn new() -> LocalQueue {
+        LocalQueue(Arc::new(ArrayQueue::new(RING_CAPACITY)))
+    }
+
+    fn stealer(&self) -> Stealer {
+        Arc::clone(&self.0)
+    }
+
+    fn push(&self, task: Task) -> Result<(), Task> {
+        self.0.push(task)
+    }
+
+    fn pop(&self) -> Option<Task> {
+        self.0.pop()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(feature = "ring-buffer")]
+fn steal_one(stealer: &Stealer) -> deque::Steal<Task> {
+    stealer
+        .pop()
+        .map_or(deque::Steal::Empty, deque::Steal::Success)
+}
+
+// The sending half of the lanes. A bounded pool caps the tasks across all of them
+// together: a sender takes a slot from `slots` before sending, and blocks or fails
+// the way a bounded channel would while none are free.
+#[derive(Clone)]
+pub(crate) struct Lanes {
+    senders: [channel::Sender<Task>; Priority::COUNT],
+    slots: Option<channel::Sender<()>>,
+}
+
+impl Lanes {
+    pub(crate) fn capacity(&self) -> Option<usize> {
+        self.slots.as_ref().and_then(channel::Sender::capacity)
+    }
+
+    pub(crate) fn send(
+        &self,
+        priority: Priority,
+        task: Task,
+    ) -> Result<(), channel::SendError<Task>> {
+        if let Some(slots) = &self.slots {
+            if slots.send(()).is_err() {
+                return Err(channel::SendError(task));
+            }
+        }
+        self.senders[priority as usize].send(task)
+    }
+
+    pub(crate) fn try_send(
+        &self,
+        priority: Priority,
+        task: Task,
+    ) -> Result<(), channel::TrySendError<Task>> {
+        if let Some(slots) = &self.slots {
+            match slots.try_send(()) {
+                Ok(()) => {}
+                Err(channel::TrySendError::Full(())) => {
+                    return Err(channel::TrySendError::Full(task))
+                }
+                Err(channel::TrySendError::Disconnected(())) => {
+                    return Err(channel::TrySendError::Disconnected(task))
+                }
+            }
+        }
+        self.senders[priority as usize]
+            .send(task)
+            .map_err(|e| channel::TrySendError::Disconnected(e.0))
+    }
+
+    pub(crate) fn send_timeout(
+        &self,
+        priority: Priority,
+        task: Task,
+        timeout: Duration,
+    ) -> Result<(), channel::SendTimeoutError<Task>> {
+        if let Some(slots) = &self.slots {
+            match slots.send_deadline((), Instant::now() + timeout) {
+                Ok(()) => {}
+                Err(channel::SendTimeoutError::Timeout(())) => {
+                    return Err(channel::SendTimeoutError::Timeout(task))
+                }
+                Err(channel::SendTimeoutError::Disconnected(())) => {
+                    return Err(channel::SendTimeoutError::Disconnected(task))
+                }
+            }
+        }
+        self.senders[priority as usize]
+            .send(task)
+            .map_err(|e| channel::SendTimeoutError::Disconnected(e.0))
+    }
+}
+
+struct Local {
+    queue: *const Queue,
+    id: usize,
+    deque: LocalQueue,
+    picks: Cell<usize>,
+}
+
+thread_local! {
+    static LOCAL: RefCell<Option<Local>> = const { RefCell::new(None) };
+}
+
+// Lanes holding at most `capacity` tasks between them, or unbounded ones.
+pub(crate) fn lanes(capacity: Option<usize>) -> (Lanes, Queue) {
+    let lanes: [_; Priority::COUNT] = std::array::from_fn(|_| channel::unbounded());
+    let senders = lanes.clone().map(|(tx, _)| tx);
+    let receivers = lanes.map(|(_, rx)| rx);
+    let (slots_tx, slots_rx) = match capacity {
+        Some(capacity) => {
+            let (tx, rx) = channel::bounded(capacity);
+            (Some(tx), Some(rx))
+        }
+        None => (None, None),
+    };
+    let lanes = Lanes {
+        senders,
+        slots: slots_tx,
+    };
+    (lanes, Queue::new(receivers, slots_rx))
+}
+
+impl Queue {
+    fn new(
+        lanes: [channel::Receiver<Task>; Priority::COUNT],
+        slots: Option<channel::Receiver<()>>,
+    ) -> Queue {
+        Queue {
+            lanes,
+            slots,
+            orphans: deque::Injector::new(),
+            stealers: ShardedLock::new(Vec::new()),
+            sleeping: AtomicUsize::new(0),
+        }
+    }
+
+    // Called on the worker thread before it starts taking jobs.
+    pub(crate) fn register(&self, id: usize) {
+        let deque = LocalQueue::new();
+        self.stealers.write().unwrap().push((id, deque.stealer()));
+        LOCAL.with(|local| {
+            *local.borrow_mut() = Some(Local {
+                queue: self as *const Queue,
+                id,
+                deque,
+                picks: Cell::new(0),
+            })
+        });
+    }
+
+    pub(crate) fn unregister(&self, id: usize) {
+        self.stealers
+            .write()
+            .unwrap()
+            .retain(|(owner, _)| *owner != id);
+        if let Some(local) = LOCAL.with(|local| local.borrow_mut().take()) {
+            while let Some(task) = local.deque.pop() {
+                self.orphans.push(task);
+            }
+        }
+    }
+
+    pub(crate) fn local_is_empty(&self) -> bool {
+        LOCAL.with(|local| local.borrow().as_ref().is_none_or(|l| l.deque.is_empty()))
+    }
+
+    // The id of the calling worker, if it is one of this queue's.
+    pub(crate) fn worker_id(&self) -> Option<usize> {
+        LOCAL.with(|local| match &*local.borrow() {
+            Some(local) if ptr::eq(local.queue, self) => Some(local.id),
+            _ => None,
+        })
+    }
+
+    // Hands the task back if the caller is not one of this queue's workers, or if
+    // another worker is asleep and would be better off receiving it.
+    pub(crate) fn push_local(&self, task: Task) -> Result<(), Task> {
+        if self.sleeping.load(Ordering::SeqCst) > 0 {
+            return Err(task);
+        }
+        LOCAL.with(|local| match &*local.borrow() {
+            Some(local) if ptr::eq(local.queue, self) => local.deque.push(task),
+            _ => Err(task),
+        })
+    }
+
+    // High priority jobs come before the Normal ones on the local deque, except
+    // that every few picks the lanes are checked lowest priority first.
+    pub(crate) fn find(&self, id: usize) -> Option<Task> {
+        let fair = LOCAL.with(|local| match &*local.borrow() {
+            Some(local) => {
+                let picks = local.picks.get().wrapping_add(1);
+                local.picks.set(picks);
+                picks % STARVATION_INTERVAL == 0
+            }
+            None => false,
+        });
+        let local = || {
+            LOCAL
+                .with(|local| local.borrow().as_ref().and_then(|l| l.deque.pop()))
+                .or_else(|| self.orphans.steal().success())
+        };
+        if fair {
+            self.recv_lanes(true)
+                .or_else(local)
+                .or_else(|| self.steal(id))
+        } else {
+            self.recv(Priority::High as usize)
+                .or_else(local)
+                .or_else(|| self.recv_lanes(false))
+                .or_else(|| self.steal(id))
+        }
+    }
+
+    fn recv(&self, lane: usize) -> Option<Task> {
+        let task = self.lanes[lane].try_recv().ok()?;
+        self.release();
+        Some(task)
+    }
+
+    // Frees the slot of a task taken off a lane.
+    fn release(&self) {
+        if let Some(slots) = &self.slots {
+            let _ = slots.try_recv();
+        }
+    }
+
+    fn recv_lanes(&self, lowest_first: bool) -> Option<Task> {
+        if lowest_first {
+            (0..Priority::COUNT).rev().find_map(|lane| self.recv(lane))
+        } else {
+            (0..Priority::COUNT).find_map(|lane| self.recv(lane))
+        }
+    }
+
+    fn steal(&self, id: usize) -> Option<Task> {
+        let stealers = self.stealers.read().unwrap();
+        iter::repeat_with(|| {
+            stealers
+                .iter()
+                .filter(|(owner, _)| *owner != id)
+                .map(|(_, stealer)| steal_one(stealer))
+                .collect::<deque::Steal<Task>>()
+        })
+        .find(|steal| !steal.is_retry())
+        .and_then(|steal| steal.success())
+    }
+
+    pub(crate) fn wait(&self, timeout: Duration) -> Result<Task, channel::RecvTimeoutError> {
+        self.sleeping.fetch_add(1, Ordering::SeqCst);
+        let mut select = channel::Select::new();
+        for lane in &self.lanes {
+            select.recv(lane);
+        }
+        let task = match select.select_timeout(timeout) {
+            // A lane only disconnects once the pool is shutting down, the others
+            // may still hold jobs that need draining.
+            Ok(operation) => {
+                let index = operation.index();
+                match operation.recv(&self.lanes[index]) {
+                    Ok(task) => {
+                        self.release();
+                        Some(task)
+                    }
+                    Err(_) => self.recv_lanes(false),
+                }
+                .ok_or(channel::RecvTimeoutError::Disconnected)
+            }
+            Err(_) => Err(channel::RecvTimeoutError::Timeout),
+        };
+        self.sleeping.fetch_sub(1, Ordering::SeqCst);
+        task
+    }
+
+    #[cfg(feature = "deterministic")]
+    pub(crate) fn try_recv(&self, lane: usize) -> Option<Task> {
+        self.recv(lane)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        let stealers = self.stealers.read().unwrap();
+        self.lanes.iter().map(|lane| lane.len()).sum::<usize>()
+            + self.orphans.len()
+            + stealers.iter().map(|(_, s)| s.len()).sum::<usize>()
+    }
+
+    // Removes every queued task without running it and returns how many there were.
+    pub(crate) fn drain(&self) -> usize {
+        let mut drained = iter::from_fn(|| self.recv_lanes(false)).count();
+        while !self.orphans.is_empty() {
+            if self.orphans.steal().is_success() {
+                drained += 1;
+            }
+        }
+        let stealers = self.stealers.read().unwrap();
+        for (_, stealer) in stealers.iter() {
+            loop {
+                match steal_one(stealer) {
+                    deque::Steal::Success(_) => drained += 1,
+                    deque::Steal::Retry => continue,
+                    deque::Steal::Empty => break,
+                }
+            }
+        }
+        drained
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use log::info;
+use thiserror::Error;
+
+use crate::{PoolCreationError, ShutdownReport, ThreadPool, ThreadPoolBuilder};
+
+// Process-wide pools looked up by name, so library code can share a pool without
+// it being passed down from `main`. In registration order.
+static POOLS: Mutex<Vec<(String, Arc<ThreadPool>)>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RegistryError {
+    #[error("A pool named {0:?} is already registered")]
+    AlreadyRegistered(String),
+    #[error(transparent)]
+    Build(#[from] PoolCreationError),
+}
+
+pub fn register(name: &str, builder: ThreadPoolBuilder) -> Result<Arc<ThreadPool>, RegistryError> {
+    let mut pools = POOLS.lock().unwrap();
+    if pools.iter().any(|(registered, _)| registered == name) {
+        return Err(RegistryError::AlreadyRegistered(name.to_string()));
+    }
+    let pool = Arc::new(builder.build()?);
+    pools.push((name.to_string(), Arc::clone(&pool)));
+    Ok(pool)
+}
+
+pub fn get(name: &str) -> Option<Arc<ThreadPool>> {
+    let pools = POOLS.lock().unwrap();
+    pools
+        .iter()
+        .find(|(registered, _)| registered == name)
+        .map(|(_, pool)| Arc::clone(pool))
+}
+
+// Returns the pool registered under `name`, building it with `builder` first if
+// there isn't one.
+pub fn get_or_register(
+    name: &str,
+    builder: impl FnOnce() -> ThreadPoolBuilder,
+) -> Result<Arc<ThreadPool>, RegistryError> {
+    let mut pools = POOLS.lock().unwrap();
+    if let Some((_, pool)) = pools.iter().find(|(registered, _)| registered == name) {
+        return Ok(Arc::clone(pool));
+    }
+    let pool = Arc::new(builder().build()?);
+    pools.push((name.to_string(), Arc::clone(&pool)));
+    Ok(pool)
+}
+
+// Shuts every registered pool down, the most recently registered first so pools
+// set up later, which may submit to earlier ones, are gone before those are. All
+// of them share `deadline`. Meant to be called once on the way out of `main`.
+pub fn shutdown_all(deadline: Instant) -> Vec<(String, ShutdownReport)> {
+    let pools = std::mem::take(&mut *POOLS.lock().unwrap());
+    pools
+        .into_iter()
+        .rev()
+        .map(|(name, pool)| {
+            info!("Shutting down {name} pool");
+            let report = pool.shutdown(deadline);
+            (name, report)
+        })
+        .collect()
+}
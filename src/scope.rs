@@ -0,0 +1,117 @@
+use std::{
+    marker::PhantomData,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::{panic_message, ExecuteError, Job, Priority, Submit, ThreadPool};
+
+// How long a worker waiting on its own scope sleeps between looking for jobs to run.
+const HELP_POLL: Duration = Duration::from_millis(1);
+
+pub struct Scope<'scope, 'env: 'scope> {
+    pool: &'scope ThreadPool,
+    state: Arc<State>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+#[derive(Default)]
+struct State {
+    pending: Mutex<usize>,
+    done: Condvar,
+    panicked: Mutex<Option<String>>,
+}
+
+// Marks a job finished when dropped, whether it ran or was thrown away unrun.
+struct Pending(Arc<State>);
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        let mut pending = self.0.pending.lock().unwrap();
+        *pending -= 1;
+        if *pending == 0 {
+            self.0.done.notify_all();
+        }
+    }
+}
+
+// Fields drop in declaration order, so a job thrown away unrun drops `f` and
+// everything it borrows before `Pending` lets `run` return.
+struct ScopedJob<F> {
+    f: F,
+    pending: Pending,
+}
+
+impl<'scope> Scope<'scope, '_> {
+    pub fn spawn<F>(&'scope self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        *self.state.pending.lock().unwrap() += 1;
+        let scoped = ScopedJob {
+            f,
+            pending: Pending(Arc::clone(&self.state)),
+        };
+        let job = move || {
+            let ScopedJob { f, pending } = scoped;
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                let mut panicked = pending.0.panicked.lock().unwrap();
+                panicked.get_or_insert_with(|| panic_message(payload.as_ref()).to_string());
+                drop(panicked);
+                // Let the worker see the panic so it is logged and counted.
+                panic::resume_unwind(payload);
+            }
+        };
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(job);
+        // SAFETY: `run` doesn't return until every job spawned on the scope has been
+        // run or dropped, so nothing borrowed for 'scope is used after it ends.
+        let job: Job = unsafe { mem::transmute(job) };
+        let task = self.pool.task(job);
+        self.pool.submit(task, Priority::Normal, Submit::Block)
+    }
+
+    fn wait(&self) {
+        let shared = &self.pool.shared;
+        let mut pending = self.state.pending.lock().unwrap();
+        while *pending > 0 {
+            // A worker blocking here would hold up the jobs it is waiting for if
+            // the pool is small, so it runs queued jobs in the meantime.
+            drop(pending);
+            let helped = shared.help();
+            pending = self.state.pending.lock().unwrap();
+            if helped || *pending == 0 {
+                continue;
+            }
+            pending = if shared.can_help() {
+                self.state.done.wait_timeout(pending, HELP_POLL).unwrap().0
+            } else {
+                self.state.done.wait(pending).unwrap()
+            };
+        }
+    }
+}
+
+pub(crate) fn run<'env, F, T>(pool: &ThreadPool, f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+{
+    let scope = Scope {
+        pool,
+        state: Arc::new(State::default()),
+        scope: PhantomData,
+        env: PhantomData,
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+    scope.wait();
+    let result = match result {
+        Ok(result) => result,
+        Err(payload) => panic::resume_unwind(payload),
+    };
+    if let Some(message) = scope.state.panicked.lock().unwrap().take() {
+        panic!("A scoped job panicked: {message}");
+    }
+    result
+}
@@ -0,0 +1,89 @@
+use std::{any::Any, cell::RefCell, ops::Deref, sync::Arc};
+
+use crate::{CancellationToken, ExecuteError, JobOptions, ThreadPool};
+
+type Factory<S> = Arc<dyn Fn() -> S + Send + Sync>;
+
+thread_local! {
+    // A worker only ever runs jobs from its own pool, so one slot per thread is
+    // enough. Dropped with the thread.
+    static STATE: RefCell<Option<Box<dyn Any>>> = const { RefCell::new(None) };
+}
+
+// A pool whose workers each own a `S`, built by the factory the first time a
+// worker runs a job and handed to every job that worker runs after that.
+pub struct StatefulPool<S> {
+    pool: ThreadPool,
+    factory: Factory<S>,
+}
+
+impl<S: 'static> StatefulPool<S> {
+    pub(crate) fn new(pool: ThreadPool, factory: Factory<S>) -> StatefulPool<S> {
+        StatefulPool { pool, factory }
+    }
+
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce(&mut S) + Send + 'static,
+    {
+        let factory = Arc::clone(&self.factory);
+        self.pool.execute(move || with_state(&factory, f))
+    }
+
+    pub fn try_execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce(&mut S) + Send + 'static,
+    {
+        let factory = Arc::clone(&self.factory);
+        self.pool.try_execute(move || with_state(&factory, f))
+    }
+
+    pub fn execute_with<F>(
+        &self,
+        options: JobOptions,
+        f: F,
+    ) -> Result<CancellationToken, ExecuteError>
+    where
+        F: FnOnce(&mut S, &CancellationToken) + Send + 'static,
+    {
+        let factory = Arc::clone(&self.factory);
+        self.pool.execute_with(options, move |token| {
+            with_state(&factory, |state| f(state, token))
+        })
+    }
+
+    pub fn try_execute_with<F>(
+        &self,
+        options: JobOptions,
+        f: F,
+    ) -> Result<CancellationToken, ExecuteError>
+    where
+        F: FnOnce(&mut S, &CancellationToken) + Send + 'static,
+    {
+        let factory = Arc::clone(&self.factory);
+        self.pool.try_execute_with(options, move |token| {
+            with_state(&factory, |state| f(state, token))
+        })
+    }
+}
+
+fn with_state<S: 'static>(factory: &Factory<S>, f: impl FnOnce(&mut S)) {
+    // Taken out for the duration of the job, so a nested job on the same worker
+    // gets a fresh state rather than a double borrow.
+    let state = STATE.with(|slot| slot.borrow_mut().take());
+    let mut state = match state.map(|state| state.downcast::<S>()) {
+        Some(Ok(state)) => state,
+        _ => Box::new(factory()),
+    };
+    // If the job panics the state is dropped, it may have been left half updated.
+    f(&mut state);
+    STATE.with(|slot| *slot.borrow_mut() = Some(state));
+}
+
+impl<S> Deref for StatefulPool<S> {
+    type Target = ThreadPool;
+
+    fn deref(&self) -> &ThreadPool {
+        &self.pool
+    }
+}
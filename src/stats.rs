@@ -0,0 +1,203 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::watchdog::Running;
+
+// Bucket `i` counts durations below 2^i microseconds, the last one catches the rest.
+const BUCKETS: usize = 24;
+
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+    total_nanos: AtomicU64,
+}
+
+impl Histogram {
+    pub(crate) fn new() -> Histogram {
+        Histogram {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            total_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.total_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            buckets: std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed)),
+            total: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    buckets: [u64; BUCKETS],
+    total: Duration,
+}
+
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count() {
+            0 => None,
+            n => Some(Duration::from_nanos(
+                (self.total.as_nanos() / u128::from(n)) as u64,
+            )),
+        }
+    }
+
+    // Upper bound of the bucket holding the given quantile, e.g. 0.99 for p99.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let target = ((count as f64 * q.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= target {
+                return Some(bucket_bound(i));
+            }
+        }
+        Some(bucket_bound(BUCKETS - 1))
+    }
+
+    // (upper bound, count) pairs for every non-empty bucket.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| **n > 0)
+            .map(|(i, n)| (bucket_bound(i), *n))
+    }
+}
+
+fn bucket_bound(bucket: usize) -> Duration {
+    Duration::from_micros(1 << bucket)
+}
+
+impl fmt::Display for HistogramSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.mean(), self.quantile(0.5), self.quantile(0.99)) {
+            (Some(mean), Some(p50), Some(p99)) => write!(
+                f,
+                "count={} mean={:?} p50<={:?} p99<={:?}",
+                self.count(),
+                mean,
+                p50,
+                p99
+            ),
+            _ => write!(f, "count=0"),
+        }
+    }
+}
+
+pub(crate) struct WorkerCounters {
+    pub(crate) active: AtomicBool,
+    pub(crate) completed: AtomicU64,
+    pub(crate) panicked: AtomicU64,
+    pub(crate) busy_nanos: AtomicU64,
+    // Set while the worker runs a job the watchdog keeps an eye on.
+    pub(crate) running: Mutex<Option<Running>>,
+}
+
+impl WorkerCounters {
+    pub(crate) fn new() -> WorkerCounters {
+        WorkerCounters {
+            active: AtomicBool::new(false),
+            completed: AtomicU64::new(0),
+            panicked: AtomicU64::new(0),
+            busy_nanos: AtomicU64::new(0),
+            running: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn snapshot(&self, id: usize) -> WorkerStats {
+        WorkerStats {
+            id,
+            active: self.active.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            panicked: self.panicked.load(Ordering::Relaxed),
+            busy: Duration::from_nanos(self.busy_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerStats {
+    pub id: usize,
+    pub active: bool,
+    pub completed: u64,
+    pub panicked: u64,
+    // Zero unless the pool was built with `track_timings(true)`.
+    pub busy: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    pub queued: usize,
+    pub active: usize,
+    pub idle: usize,
+    // Totals over the pool's lifetime, including workers that have since retired.
+    pub completed: usize,
+    pub panicked: usize,
+    // Jobs cancelled before they started, and jobs that ran past their timeout.
+    pub cancelled: usize,
+    pub timed_out: usize,
+    // Times the watchdog found every worker stuck on a slow job.
+    pub starved: usize,
+    pub workers: Vec<WorkerStats>,
+    // Empty unless the pool was built with `track_timings(true)`.
+    pub queue_wait: HistogramSnapshot,
+    pub execution: HistogramSnapshot,
+}
+
+impl PoolStats {
+    pub fn busy(&self) -> Duration {
+        self.workers.iter().map(|w| w.busy).sum()
+    }
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "queued: {}", self.queued)?;
+        writeln!(f, "workers: {} active, {} idle", self.active, self.idle)?;
+        writeln!(
+            f,
+            "jobs: {} completed, {} panicked, {} cancelled, {} timed out",
+            self.completed, self.panicked, self.cancelled, self.timed_out
+        )?;
+        writeln!(f, "starved: {}", self.starved)?;
+        writeln!(f, "busy: {:?}", self.busy())?;
+        writeln!(f, "queue wait: {}", self.queue_wait)?;
+        writeln!(f, "execution: {}", self.execution)?;
+        for worker in &self.workers {
+            writeln!(
+                f,
+                "worker {}: {} completed={} panicked={} busy={:?}",
+                worker.id,
+                if worker.active { "active" } else { "idle" },
+                worker.completed,
+                worker.panicked,
+                worker.busy
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use crossbeam::channel;
+use log::{error, warn};
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::BinaryHeap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{queue::Lanes, Job, Priority, Shared, Task};
+
+// How long a due job waits before the timer retries a full queue.
+const RETRY_FULL: Duration = Duration::from_millis(10);
+
+type Periodic = Arc<dyn Fn() + Send + Sync + 'static>;
+
+enum Scheduled {
+    Once(Job),
+    Every {
+        job: Periodic,
+        interval: Duration,
+        running: Arc<AtomicBool>,
+    },
+}
+
+struct Entry {
+    deadline: Instant,
+    // Breaks ties so entries with the same deadline fire in the order they were added.
+    seq: u64,
+    cancelled: Arc<AtomicBool>,
+    scheduled: Scheduled,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed so the BinaryHeap pops the earliest deadline first.
+impl Ord for Entry {
+    fn cmp(&self, other: &Entry) -> CmpOrdering {
+        (other.deadline, other.seq).cmp(&(self.deadline, self.seq))
+    }
+}
+
+enum Command {
+    Schedule(Entry),
+    Stop,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduleHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ScheduleHandle {
+    // Stops future runs. A run that has already started is not interrupted.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+pub(crate) struct Timer {
+    commands: channel::Sender<Command>,
+    thread: Option<JoinHandle<()>>,
+    seq: u64,
+}
+
+impl Timer {
+    pub(crate) fn start(shared: Arc<Shared>, lanes: Lanes) -> io::Result<Timer> {
+        let (commands, rx) = channel::unbounded();
+        let mut builder = thread::Builder::new();
+        if let Some(prefix) = &shared.config.name_prefix {
+            builder = builder.name(format!("{prefix}-timer"));
+        }
+        let thread = builder.spawn(move || run(shared, lanes, rx))?;
+        Ok(Timer {
+            commands,
+            thread: Some(thread),
+            seq: 0,
+        })
+    }
+
+    pub(crate) fn schedule_once(&mut self, delay: Duration, job: Job) -> ScheduleHandle {
+        self.schedule(delay, Scheduled::Once(job))
+    }
+
+    pub(crate) fn schedule_every(&mut self, interval: Duration, job: Periodic) -> ScheduleHandle {
+        self.schedule(
+            interval,
+            Scheduled::Every {
+                job,
+                interval,
+                running: Arc::new(AtomicBool::new(false)),
+            },
+        )
+    }
+
+    fn schedule(&mut self, delay: Duration, scheduled: Scheduled) -> ScheduleHandle {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.seq += 1;
+        let entry = Entry {
+            deadline: Instant::now() + delay,
+            seq: self.seq,
+            cancelled: Arc::clone(&cancelled),
+            scheduled,
+        };
+        if self.commands.send(Command::Schedule(entry)).is_err() {
+            error!("Timer thread has stopped, dropping scheduled job");
+        }
+        ScheduleHandle { cancelled }
+    }
+
+    // Drops every pending entry and waits for the timer thread to exit.
+    pub(crate) fn stop(&mut self) {
+        let _ = self.commands.send(Command::Stop);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("Timer thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run(shared: Arc<Shared>, lanes: Lanes, rx: channel::Receiver<Command>) {
+    let mut heap: BinaryHeap<Entry> = BinaryHeap::new();
+    loop {
+        let command = match heap.peek() {
+            Some(next) => rx.recv_deadline(next.deadline),
+            None => rx
+                .recv()
+                .map_err(|_| channel::RecvTimeoutError::Disconnected),
+        };
+        match command {
+            Ok(Command::Schedule(entry)) => heap.push(entry),
+            Ok(Command::Stop) | Err(channel::RecvTimeoutError::Disconnected) => break,
+            Err(channel::RecvTimeoutError::Timeout) => {
+                let now = Instant::now();
+                while heap.peek().is_some_and(|next| next.deadline <= now) {
+                    let entry = heap.pop().unwrap();
+                    if let Some(entry) = fire(&shared, &lanes, entry, now) {
+                        heap.push(entry);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Submits a due entry to the pool and returns it if it should fire again.
+fn fire(shared: &Shared, lanes: &Lanes, mut entry: Entry, now: Instant) -> Option<Entry> {
+    if entry.cancelled.load(Ordering::SeqCst) {
+        return None;
+    }
+    let timed = shared.timed.load(Ordering::Relaxed);
+    match entry.scheduled {
+        Scheduled::Once(job) => {
+            let cancelled = Arc::clone(&entry.cancelled);
+            let task = Task::new(
+                Box::new(move || {
+                    if !cancelled.load(Ordering::SeqCst) {
+                        job();
+                    }
+                }),
+                timed,
+            );
+            match lanes.try_send(Priority::Normal, task) {
+                Ok(()) => None,
+                Err(channel::TrySendError::Full(task)) => {
+                    entry.scheduled = Scheduled::Once(task.job);
+                    entry.deadline = now + RETRY_FULL;
+                    Some(entry)
+                }
+                Err(channel::TrySendError::Disconnected(_)) => None,
+            }
+        }
+        Scheduled::Every {
+            ref job,
+            interval,
+            ref running,
+        } => {
+            // Skip this tick rather than pile up runs of a job that is still going.
+            if !running.swap(true, Ordering::SeqCst) {
+                let job = Arc::clone(job);
+                let flag = Arc::clone(running);
+                let cancelled = Arc::clone(&entry.cancelled);
+                let task = Task::new(
+                    Box::new(move || {
+                        let _reset = ResetOnDrop(&flag);
+                        if !cancelled.load(Ordering::SeqCst) {
+                            job();
+                        }
+                    }),
+                    timed,
+                );
+                if let Err(e) = lanes.try_send(Priority::Normal, task) {
+                    running.store(false, Ordering::SeqCst);
+                    warn!("Skipping periodic job tick: {e}");
+                }
+            }
+            // Only an interval of centuries runs off the end of `Instant`.
+            let next = entry.deadline.checked_add(interval)?;
+            entry.deadline = if next <= now {
+                now.checked_add(interval)?
+            } else {
+                next
+            };
+            Some(entry)
+        }
+    }
+}
+
+// Clears the running flag even if the periodic job panics.
+struct ResetOnDrop<'a>(&'a AtomicBool);
+
+impl Drop for ResetOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
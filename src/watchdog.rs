@@ -0,0 +1,147 @@
+use crossbeam::channel;
+use log::{error, info, warn};
+use std::{
+    io,
+    sync::{atomic::Ordering, Arc},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{CancellationToken, Shared};
+
+// How often running jobs are checked against their deadlines and the slow job
+// threshold.
+const SCAN_INTERVAL: Duration = Duration::from_millis(50);
+
+pub(crate) struct Running {
+    pub(crate) started: Instant,
+    pub(crate) label: Option<Arc<str>>,
+    pub(crate) deadline: Option<Deadline>,
+    // Already warned about.
+    pub(crate) slow: bool,
+}
+
+pub(crate) struct Deadline {
+    pub(crate) at: Instant,
+    pub(crate) token: CancellationToken,
+    pub(crate) overdue: bool,
+}
+
+pub(crate) struct Watchdog {
+    stop: Option<channel::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub(crate) fn start(shared: Arc<Shared>) -> io::Result<Watchdog> {
+        let (stop, rx) = channel::bounded::<()>(0);
+        let mut builder = thread::Builder::new();
+        if let Some(prefix) = &shared.config.name_prefix {
+            builder = builder.name(format!("{prefix}-watchdog"));
+        }
+        let thread = builder.spawn(move || {
+            let mut starved = false;
+            while let Err(channel::RecvTimeoutError::Timeout) = rx.recv_timeout(SCAN_INTERVAL) {
+                scan(&shared, &mut starved);
+            }
+        })?;
+        Ok(Watchdog {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    pub(crate) fn stop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("Watchdog thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Flags jobs that ran past their deadline and adds a worker to stand in for each,
+// up to `max` stand-ins, and warns about slow jobs. `starved` carries over between
+// scans so a starved pool is reported once rather than on every scan.
+fn scan(shared: &Arc<Shared>, starved: &mut bool) {
+    let now = Instant::now();
+    let threshold = shared.config.slow_threshold;
+    let mut overdue = 0;
+    let mut slow = 0;
+    let workers = shared.workers.lock().unwrap();
+    for worker in workers.iter() {
+        let mut running = worker.counters.running.lock().unwrap();
+        let Some(running) = running.as_mut() else {
+            continue;
+        };
+        let label = running.label.as_deref().unwrap_or("<unlabelled>");
+        let elapsed = now - running.started;
+        if threshold.is_some_and(|threshold| elapsed >= threshold) {
+            slow += 1;
+            if !running.slow {
+                running.slow = true;
+                warn!(
+                    "Worker {} job {} has been running for {:?}",
+                    worker.id, label, elapsed
+                );
+            }
+        }
+        match running.deadline.as_mut() {
+            Some(deadline) if !deadline.overdue && now >= deadline.at => {
+                // Counted while holding the lock so the worker can't finish and
+                // uncount it first.
+                deadline.overdue = true;
+                deadline.token.cancel();
+                shared.stuck.fetch_add(1, Ordering::SeqCst);
+                overdue += 1;
+                warn!(
+                    "Worker {} job {} exceeded its deadline by {:?}, cancelling it",
+                    worker.id,
+                    label,
+                    now - deadline.at
+                );
+            }
+            _ => {}
+        }
+    }
+    let size = workers.len();
+    drop(workers);
+    let was_starved = *starved;
+    *starved = size > 0 && slow == size;
+    if *starved && !was_starved {
+        shared.starved.fetch_add(1, Ordering::Relaxed);
+        error!("Pool starved: all {} workers are stuck on slow jobs", size);
+    } else if was_starved && !*starved {
+        info!("Pool no longer starved");
+    }
+    if *starved {
+        // Nothing dequeues while every worker is stuck, so the autoscaler would
+        // never notice the backlog on its own.
+        Shared::scale_up(shared, Duration::MAX);
+    }
+    if overdue == 0 {
+        return;
+    }
+    shared.timed_out.fetch_add(overdue, Ordering::Relaxed);
+    let mut workers = shared.workers.lock().unwrap();
+    if shared.shutting_down.load(Ordering::SeqCst) || shared.is_deterministic() {
+        return;
+    }
+    let limit = shared.max.load(Ordering::SeqCst) + shared.stand_ins();
+    for _ in 0..overdue {
+        if workers.len() >= limit {
+            warn!("Too many workers stuck past their deadline, not replacing them");
+            break;
+        }
+        if let Err(e) = Shared::spawn_worker(shared, &mut workers) {
+            error!("Failed to spawn a replacement worker: {e}");
+        }
+    }
+}
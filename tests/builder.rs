@@ -0,0 +1,28 @@
+use simple_http_server::{Autoscale, PoolCreationError, ThreadPool};
+
+#[test]
+fn size_must_fit_the_autoscaling_range() {
+    let build = |size| {
+        ThreadPool::builder()
+            .size(size)
+            .autoscale(Autoscale::new(2, 4))
+            .build()
+    };
+    for size in [1, 5] {
+        assert!(
+            matches!(
+                build(size),
+                Err(PoolCreationError::SizeOutOfRange { min: 2, max: 4, .. })
+            ),
+            "{size}"
+        );
+    }
+    for size in 2..=4 {
+        assert_eq!(build(size).unwrap().size(), size);
+    }
+    let pool = ThreadPool::builder()
+        .autoscale(Autoscale::new(2, 4))
+        .build()
+        .unwrap();
+    assert_eq!(pool.size(), 2);
+}
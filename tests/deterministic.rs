@@ -0,0 +1,89 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use simple_http_server::{ExecuteError, Priority, ThreadPool};
+
+// Submits ten jobs that record their index and returns the order they ran in.
+fn run_order(seed: u64) -> Vec<usize> {
+    let pool = ThreadPool::builder().deterministic(seed).build().unwrap();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    for i in 0..10 {
+        let order = Arc::clone(&order);
+        pool.execute(move || order.lock().unwrap().push(i)).unwrap();
+    }
+    assert_eq!(pool.run_until_idle(), 10);
+    Arc::try_unwrap(order).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn order_is_fixed_by_the_seed() {
+    let first = run_order(7);
+    assert_eq!(first, run_order(7));
+    let mut sorted = first.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..10).collect::<Vec<_>>());
+    assert!((0..20).any(|seed| run_order(seed) != first));
+}
+
+#[test]
+fn higher_priority_runs_first() {
+    let pool = ThreadPool::builder().deterministic(1).build().unwrap();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    for priority in [Priority::Low, Priority::Normal, Priority::High] {
+        let order = Arc::clone(&order);
+        pool.execute_with_priority(move || order.lock().unwrap().push(priority), priority)
+            .unwrap();
+    }
+    pool.run_until_idle();
+    assert_eq!(
+        *order.lock().unwrap(),
+        [Priority::High, Priority::Normal, Priority::Low]
+    );
+}
+
+#[test]
+fn jobs_submitted_by_jobs_run_too() {
+    let pool = Arc::new(ThreadPool::builder().deterministic(3).build().unwrap());
+    let ran = Arc::new(Mutex::new(0));
+    let (inner, count) = (Arc::clone(&pool), Arc::clone(&ran));
+    pool.execute(move || {
+        for _ in 0..3 {
+            let count = Arc::clone(&count);
+            inner.execute(move || *count.lock().unwrap() += 1).unwrap();
+        }
+    })
+    .unwrap();
+    assert_eq!(pool.run_until_idle(), 4);
+    assert_eq!(*ran.lock().unwrap(), 3);
+}
+
+#[test]
+fn full_queue_fails_instead_of_blocking() {
+    let pool = ThreadPool::builder()
+        .deterministic(0)
+        .queue_capacity(2)
+        .build()
+        .unwrap();
+    pool.execute(|| {}).unwrap();
+    pool.execute(|| {}).unwrap();
+    assert!(matches!(pool.execute(|| {}), Err(ExecuteError::QueueFull)));
+    assert_eq!(pool.run_until_idle(), 2);
+    pool.execute(|| {}).unwrap();
+}
+
+#[test]
+fn shutdown_runs_queued_jobs() {
+    let pool = ThreadPool::builder().deterministic(5).build().unwrap();
+    let ran = Arc::new(Mutex::new(0));
+    for _ in 0..5 {
+        let ran = Arc::clone(&ran);
+        pool.execute(move || *ran.lock().unwrap() += 1).unwrap();
+    }
+    let report = pool.shutdown(Instant::now() + Duration::from_secs(1));
+    assert_eq!(report.completed, 5);
+    assert_eq!(report.abandoned, 0);
+    assert_eq!(*ran.lock().unwrap(), 5);
+    assert!(matches!(pool.execute(|| {}), Err(ExecuteError::ShutDown)));
+}
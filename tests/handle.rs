@@ -0,0 +1,41 @@
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use simple_http_server::{JobHandle, JoinError, ThreadPool};
+
+fn wait_finished<T>(handle: &JobHandle<T>) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !handle.is_finished() {
+        assert!(Instant::now() < deadline, "job never finished");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn is_finished_agrees_with_try_join() {
+    let pool = ThreadPool::new(1);
+    let (release, blocked) = mpsc::channel::<()>();
+    let blocker = pool.spawn(move || blocked.recv().is_ok()).unwrap();
+    let queued = pool.spawn(|| 1).unwrap();
+    assert!(!blocker.is_finished());
+    assert!(!queued.is_finished());
+    assert!(queued.try_join().is_none());
+
+    queued.cancel();
+    release.send(()).unwrap();
+    wait_finished(&blocker);
+    assert!(blocker.join().unwrap());
+    // A cancelled job never sends a result, it only drops its sender.
+    wait_finished(&queued);
+    assert!(matches!(queued.try_join(), Some(Err(JoinError::Cancelled))));
+
+    let panicked = pool.spawn(|| panic!("boom")).unwrap();
+    wait_finished(&panicked);
+    assert!(matches!(
+        panicked.try_join(),
+        Some(Err(JoinError::Panicked(_)))
+    ));
+}
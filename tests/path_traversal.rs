@@ -0,0 +1,206 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use simple_http_server::http::{
+    path::{self, PathError},
+    StaticFiles, Status,
+};
+
+// Payloads that try to reach a file outside the document root.
+const ESCAPES: &[&str] = &[
+    "../secret.txt",
+    "../../etc/passwd",
+    "a/../../secret.txt",
+    "blog/../../secret.txt",
+    "./../secret.txt",
+    "..\\secret.txt",
+    "blog\\..\\..\\secret.txt",
+    "%2e%2e/secret.txt",
+    "%2E%2E/secret.txt",
+    "..%2fsecret.txt",
+    "..%2Fsecret.txt",
+    "%2e%2e%2fsecret.txt",
+    "..%5csecret.txt",
+    "%2e%2e%5csecret.txt",
+    ".%2e/secret.txt",
+    "%2e./secret.txt",
+    "blog/%2e%2e/%2e%2e/secret.txt",
+    "//../secret.txt",
+    "/../secret.txt",
+    "site.css/..%2f..%2fsecret.txt",
+];
+
+// Payloads that don't decode to a usable path at all.
+const MALFORMED: &[&str] = &[
+    "secret.txt%00.html",
+    "%00",
+    "%c0%ae%c0%ae/secret.txt",
+    "%c0%af",
+    "%e0%80%ae",
+    "%zz",
+    "%2",
+    "%",
+    "%%32%65",
+    "%+1",
+    "a%+1b",
+];
+
+// Payloads that look suspicious but stay inside the root.
+const CONTAINED: &[(&str, &str)] = &[
+    ("blog/../site.css", "site.css"),
+    ("blog/./hello", "blog/hello.html"),
+    ("./blog/", "blog/index.html"),
+    ("blog//hello.html", "blog/hello.html"),
+    ("site.css/", "site.css"),
+    ("blog/%2e%2e/site.css", "site.css"),
+    ("%62log/hello", "blog/hello.html"),
+    ("blog\\hello.html", "blog/hello.html"),
+    ("", "index.html"),
+];
+
+// Harmless names that merely contain dots.
+const MISSING: &[&str] = &["....//", "...", "..secret.txt", "secret.txt..", "blog/..."];
+
+// Paths that continue past a regular file.
+const THROUGH_FILES: &[&str] = &[
+    "site.css/x",
+    "index.html/index",
+    "blog/hello.html/x/y",
+    "blog/hello/x",
+];
+
+struct Fixture {
+    dir: PathBuf,
+    files: StaticFiles,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Fixture {
+        let dir = std::env::temp_dir().join(format!("path-traversal-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let root = dir.join("root");
+        fs::create_dir_all(root.join("blog")).unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        fs::write(root.join("index.html"), "index").unwrap();
+        fs::write(root.join("site.css"), "css").unwrap();
+        fs::write(root.join("blog/index.html"), "blog").unwrap();
+        fs::write(root.join("blog/hello.html"), "hello").unwrap();
+        let files = StaticFiles::new(&root);
+        Fixture { dir, files }
+    }
+
+    fn root(&self) -> PathBuf {
+        fs::canonicalize(self.dir.join("root")).unwrap()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn escapes_are_forbidden() {
+    let fixture = Fixture::new("escapes");
+    for payload in ESCAPES {
+        let status = fixture.files.serve(payload).status;
+        assert_eq!(status, Status::Forbidden, "{payload:?}");
+    }
+}
+
+#[test]
+fn malformed_paths_are_bad_requests() {
+    let fixture = Fixture::new("malformed");
+    for payload in MALFORMED {
+        let status = fixture.files.serve(payload).status;
+        assert_eq!(status, Status::BadRequest, "{payload:?}");
+    }
+}
+
+#[test]
+fn contained_paths_are_served() {
+    let fixture = Fixture::new("contained");
+    let root = fixture.root();
+    for (payload, expected) in CONTAINED {
+        let resolved = fixture.files.resolve(payload).unwrap();
+        assert_eq!(resolved, Some(root.join(expected)), "{payload:?}");
+        assert_eq!(fixture.files.serve(payload).status, Status::Ok);
+    }
+}
+
+#[test]
+fn dotted_names_are_not_found() {
+    let fixture = Fixture::new("dotted");
+    for payload in MISSING {
+        let status = fixture.files.serve(payload).status;
+        assert_eq!(status, Status::NotFound, "{payload:?}");
+    }
+}
+
+#[test]
+fn paths_through_files_are_not_found() {
+    let fixture = Fixture::new("through-files");
+    for payload in THROUGH_FILES {
+        let status = fixture.files.serve(payload).status;
+        assert_eq!(status, Status::NotFound, "{payload:?}");
+    }
+}
+
+#[test]
+fn overlong_names_are_not_found() {
+    let fixture = Fixture::new("overlong");
+    for payload in ["a".repeat(300), format!("blog/{}/x", "b".repeat(4096))] {
+        let status = fixture.files.serve(&payload).status;
+        assert_eq!(status, Status::NotFound, "{} bytes", payload.len());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_the_root_are_forbidden() {
+    use std::os::unix::fs::symlink;
+
+    let fixture = Fixture::new("symlinks");
+    let root = fixture.dir.join("root");
+    symlink(fixture.dir.join("secret.txt"), root.join("leak.txt")).unwrap();
+    symlink(&fixture.dir, root.join("up")).unwrap();
+    symlink(root.join("site.css"), root.join("style.css")).unwrap();
+    for payload in ["leak.txt", "up/secret.txt"] {
+        let status = fixture.files.serve(payload).status;
+        assert_eq!(status, Status::Forbidden, "{payload:?}");
+    }
+    let resolved = fixture.files.resolve("style.css").unwrap();
+    assert_eq!(resolved, Some(fixture.root().join("site.css")));
+}
+
+#[test]
+fn missing_root_serves_nothing() {
+    let files = StaticFiles::new(Path::new("/nonexistent/document/root"));
+    assert_eq!(files.serve("index.html").status, Status::NotFound);
+    assert_eq!(files.serve("../secret.txt").status, Status::Forbidden);
+}
+
+#[test]
+fn sanitize_corpus() {
+    for payload in ESCAPES {
+        assert!(matches!(path::sanitize(payload), Err(PathError::Traversal)));
+    }
+    for payload in MALFORMED {
+        let error = path::sanitize(payload).unwrap_err();
+        assert_eq!(error.status(), Status::BadRequest, "{payload:?}");
+    }
+    assert!(matches!(path::decode("a%00b"), Err(PathError::Nul)));
+    assert!(matches!(path::decode("%c0%ae"), Err(PathError::Utf8)));
+    assert!(matches!(path::decode("%4"), Err(PathError::Encoding)));
+    assert!(matches!(path::decode("%-1"), Err(PathError::Encoding)));
+    assert_eq!(path::decode("a%20b+c").unwrap(), "a b+c");
+    assert_eq!(
+        path::normalize("/a/./b//../c/").unwrap(),
+        Path::new("a").join("c")
+    );
+    assert_eq!(path::normalize("a/..").unwrap(), PathBuf::new());
+}
@@ -0,0 +1,104 @@
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use simple_http_server::{ExecuteError, Priority, ThreadPool};
+
+// Occupies the pool's only worker until the returned sender is dropped or sent to.
+fn block_worker(pool: &ThreadPool) -> mpsc::Sender<()> {
+    let (started, running) = mpsc::channel();
+    let (release, blocked) = mpsc::channel::<()>();
+    pool.execute(move || {
+        started.send(()).unwrap();
+        let _ = blocked.recv();
+    })
+    .unwrap();
+    running.recv().unwrap();
+    release
+}
+
+#[test]
+fn capacity_is_shared_across_priorities() {
+    let pool = ThreadPool::build_bounded(1, 3).unwrap();
+    assert_eq!(pool.capacity(), Some(3));
+    let release = block_worker(&pool);
+    let (done, finished) = mpsc::channel();
+    for priority in [Priority::High, Priority::Normal, Priority::Low] {
+        let done = done.clone();
+        pool.try_execute_with_priority(move || done.send(()).unwrap(), priority)
+            .unwrap();
+    }
+    assert_eq!(pool.queued(), 3);
+    for priority in [Priority::High, Priority::Normal, Priority::Low] {
+        let result = pool.try_execute_with_priority(|| {}, priority);
+        assert!(
+            matches!(result, Err(ExecuteError::QueueFull)),
+            "{priority:?}"
+        );
+    }
+    assert!(matches!(
+        pool.try_execute(|| {}),
+        Err(ExecuteError::QueueFull)
+    ));
+    release.send(()).unwrap();
+    for _ in 0..3 {
+        finished.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+}
+
+#[test]
+fn execute_timeout_gives_up_on_a_full_queue() {
+    let pool = ThreadPool::build_bounded(1, 1).unwrap();
+    let release = block_worker(&pool);
+    pool.try_execute(|| {}).unwrap();
+    let started = Instant::now();
+    let result = pool.execute_timeout(|| {}, Duration::from_millis(20));
+    assert!(matches!(result, Err(ExecuteError::Timeout)));
+    assert!(started.elapsed() >= Duration::from_millis(20));
+
+    // Once the worker frees a slot, a waiting submission gets in.
+    release.send(()).unwrap();
+    let (done, finished) = mpsc::channel();
+    pool.execute_timeout(move || done.send(()).unwrap(), Duration::from_secs(5))
+        .unwrap();
+    finished.recv_timeout(Duration::from_secs(5)).unwrap();
+}
+
+// Queues `jobs` behind a blocked worker, then returns the order they ran in.
+fn run_order(pool: &ThreadPool, jobs: &[(&'static str, Priority)]) -> Vec<&'static str> {
+    let release = block_worker(pool);
+    let (done, finished) = mpsc::channel();
+    for &(name, priority) in jobs {
+        let done = done.clone();
+        pool.execute_with_priority(move || done.send(name).unwrap(), priority)
+            .unwrap();
+    }
+    drop(release);
+    (0..jobs.len())
+        .map(|_| finished.recv_timeout(Duration::from_secs(5)).unwrap())
+        .collect()
+}
+
+#[test]
+fn high_jobs_run_before_queued_low_jobs() {
+    let pool = ThreadPool::new(1);
+    let mut jobs = vec![("low", Priority::Low); 4];
+    jobs.extend([("high", Priority::High); 4]);
+    let order = run_order(&pool, &jobs);
+    // Eight picks hold at most one fairness pick, which may take a Low job early.
+    let last_high = order.iter().rposition(|&name| name == "high").unwrap();
+    let early_lows = order[..last_high].iter().filter(|&&name| name == "low");
+    assert!(early_lows.count() <= 1, "{order:?}");
+}
+
+#[test]
+fn low_jobs_run_under_a_stream_of_high_jobs() {
+    let pool = ThreadPool::new(1);
+    let mut jobs = vec![("low", Priority::Low)];
+    jobs.extend([("high", Priority::High); 100]);
+    let order = run_order(&pool, &jobs);
+    // The High lane never empties, so only a fairness pick reaches the Low job.
+    let low = order.iter().position(|&name| name == "low").unwrap();
+    assert!(low < 8, "Low job ran {low} jobs in");
+}
@@ -0,0 +1,117 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use simple_http_server::ThreadPool;
+
+#[test]
+fn jobs_borrow_from_the_stack() {
+    let pool = ThreadPool::new(4);
+    let mut squares = vec![0; 64];
+    let offset = 1;
+    pool.scope(|s| {
+        for (i, square) in squares.iter_mut().enumerate() {
+            s.spawn(move || *square = i * i + offset).unwrap();
+        }
+    });
+    assert!(squares.iter().enumerate().all(|(i, &x)| x == i * i + 1));
+}
+
+#[test]
+fn scope_returns_the_closure_result() {
+    let pool = ThreadPool::new(2);
+    let total = Mutex::new(0);
+    let answer = pool.scope(|s| {
+        for i in 1..=4 {
+            let total = &total;
+            s.spawn(move || *total.lock().unwrap() += i).unwrap();
+        }
+        42
+    });
+    assert_eq!(answer, 42);
+    assert_eq!(*total.lock().unwrap(), 10);
+}
+
+#[test]
+fn a_panicking_job_panics_the_scope() {
+    let pool = ThreadPool::new(2);
+    let finished = Mutex::new(0);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        pool.scope(|s| {
+            s.spawn(|| panic!("boom")).unwrap();
+            for _ in 0..4 {
+                s.spawn(|| *finished.lock().unwrap() += 1).unwrap();
+            }
+        })
+    }));
+    let payload = result.unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert_eq!(message, "A scoped job panicked: boom");
+    // The other jobs still ran before the scope gave up.
+    assert_eq!(*finished.lock().unwrap(), 4);
+    assert_eq!(pool.panic_count(), 1);
+}
+
+#[test]
+fn nested_scope_on_a_single_worker() {
+    let pool = ThreadPool::new(1);
+    let mut values = [0; 8];
+    pool.scope(|outer| {
+        outer
+            .spawn(|| {
+                // The only worker waits here, so it has to run the inner jobs itself.
+                pool.scope(|inner| {
+                    for (i, value) in values.iter_mut().enumerate() {
+                        inner.spawn(move || *value = i).unwrap();
+                    }
+                });
+            })
+            .unwrap();
+    });
+    assert_eq!(values, [0, 1, 2, 3, 4, 5, 6, 7]);
+}
+
+// Records its drop after a delay, long enough for `scope` to return first if it
+// didn't wait for the job's captures to be dropped.
+struct SlowDrop<'a>(&'a Mutex<Vec<&'static str>>);
+
+impl Drop for SlowDrop<'_> {
+    fn drop(&mut self) {
+        thread::sleep(Duration::from_millis(50));
+        self.0.lock().unwrap().push("dropped");
+    }
+}
+
+#[test]
+fn jobs_dropped_by_shutdown_release_their_borrows_first() {
+    let pool = ThreadPool::new(1);
+    let (release, blocked) = mpsc::channel::<()>();
+    pool.execute(move || {
+        let _ = blocked.recv();
+    })
+    .unwrap();
+    let log = Mutex::new(Vec::new());
+    thread::scope(|threads| {
+        threads.spawn(|| {
+            while pool.queued() == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let report = pool.shutdown(Instant::now());
+            assert_eq!(report.abandoned, 1);
+        });
+        let guard = SlowDrop(&log);
+        pool.scope(|s| {
+            s.spawn(move || {
+                let _guard = guard;
+                unreachable!("the only worker is blocked");
+            })
+            .unwrap();
+        });
+        log.lock().unwrap().push("scope returned");
+    });
+    release.send(()).unwrap();
+    assert_eq!(*log.lock().unwrap(), ["dropped", "scope returned"]);
+}
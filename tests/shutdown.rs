@@ -0,0 +1,42 @@
+use std::{
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
+};
+
+use simple_http_server::ThreadPool;
+
+#[test]
+fn a_job_can_drop_the_last_handle() {
+    let pool = Arc::new(ThreadPool::new(2));
+    let (go, wait) = mpsc::channel();
+    let (done, finished) = mpsc::channel();
+    let handle = Arc::clone(&pool);
+    pool.execute(move || {
+        wait.recv().unwrap();
+        drop(handle);
+        done.send(()).unwrap();
+    })
+    .unwrap();
+    drop(pool);
+    go.send(()).unwrap();
+    // A panicking drop would be caught by the worker and never reach `send`.
+    finished.recv_timeout(Duration::from_secs(5)).unwrap();
+}
+
+#[test]
+fn a_job_can_shut_down_its_own_pool() {
+    let pool = Arc::new(ThreadPool::new(2));
+    let (done, finished) = mpsc::channel();
+    let handle = Arc::clone(&pool);
+    pool.execute(move || {
+        let started = Instant::now();
+        let report = handle.shutdown(started + Duration::from_secs(5));
+        done.send((report.abandoned, started.elapsed())).unwrap();
+    })
+    .unwrap();
+    let (abandoned, elapsed) = finished.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(abandoned, 0);
+    // The job didn't wait out the deadline for its own worker to exit.
+    assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    assert!(pool.is_shut_down());
+}
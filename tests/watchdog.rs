@@ -0,0 +1,100 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use simple_http_server::{JobOptions, JoinError, ThreadPool};
+
+// Polls `condition` until it holds, failing the test after a few seconds.
+fn wait_until(what: &str, mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+// Occupies the pool's only worker until the returned sender is dropped.
+fn block_worker(pool: &ThreadPool) -> mpsc::Sender<()> {
+    let (started, running) = mpsc::channel();
+    let (release, blocked) = mpsc::channel::<()>();
+    pool.execute(move || {
+        started.send(()).unwrap();
+        let _ = blocked.recv();
+    })
+    .unwrap();
+    running.recv().unwrap();
+    release
+}
+
+#[test]
+fn cancelled_queued_jobs_never_run() {
+    let pool = ThreadPool::new(1);
+    let release = block_worker(&pool);
+    let ran = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&ran);
+    let token = pool
+        .execute_with(JobOptions::new(), move |_| {
+            flag.store(true, Ordering::SeqCst)
+        })
+        .unwrap();
+    let handle = pool.spawn_with(JobOptions::new(), |_| 42).unwrap();
+    token.cancel();
+    handle.cancel();
+    drop(release);
+
+    assert!(matches!(handle.join(), Err(JoinError::Cancelled)));
+    // Runs after both cancelled jobs were taken off the queue.
+    pool.spawn(|| ()).unwrap().join().unwrap();
+    assert!(!ran.load(Ordering::SeqCst));
+    assert_eq!(pool.stats().cancelled, 2);
+}
+
+#[test]
+fn overdue_jobs_are_cancelled_and_counted() {
+    let pool = ThreadPool::new(2);
+    let options = JobOptions::new()
+        .timeout(Duration::from_millis(20))
+        .label("overdue");
+    let handle = pool
+        .spawn_with(options, |token| {
+            let started = Instant::now();
+            while !token.is_cancelled() && started.elapsed() < Duration::from_secs(5) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            token.is_cancelled()
+        })
+        .unwrap();
+    assert!(handle.join().unwrap());
+    wait_until("timed_out", || pool.stats().timed_out == 1);
+}
+
+#[test]
+fn stand_ins_are_capped_and_retire() {
+    let pool = ThreadPool::new(2);
+    let options = JobOptions::new().timeout(Duration::from_millis(20));
+    // Jobs that ignore their token and stay stuck until released.
+    let releases: Vec<_> = (0..4)
+        .map(|_| {
+            let (release, blocked) = mpsc::channel::<()>();
+            pool.execute_with(options.clone(), move |_| {
+                let _ = blocked.recv();
+            })
+            .unwrap();
+            release
+        })
+        .collect();
+    // The first two stuck jobs get stand-ins, which pick up the other two.
+    wait_until("every job to time out", || pool.stats().timed_out == 4);
+    assert_eq!(pool.size(), 4);
+    // Later scans don't add workers beyond `max` stand-ins.
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(pool.size(), 4);
+
+    drop(releases);
+    wait_until("the pool to shrink back", || pool.size() == 2);
+}